@@ -1,6 +1,11 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure().compile(
-        &["./skywalking-data-collect-protocol/language-agent/Tracing.proto"],
+        &[
+            "./skywalking-data-collect-protocol/language-agent/Tracing.proto",
+            "./skywalking-data-collect-protocol/event/Event.proto",
+            "./skywalking-data-collect-protocol/profile/Profile.proto",
+            "./skywalking-data-collect-protocol/management/Management.proto",
+        ],
         &["./skywalking-data-collect-protocol"],
     )?;
     Ok(())
@@ -0,0 +1,127 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use once_cell::sync::OnceCell;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Curated subset of SkyWalking's component-libraries.yml.
+/// https://github.com/apache/skywalking/blob/6452e0c2d983c85c392602d50436e8d8e421fec9/oap-server/server-starter/src/main/resources/component-libraries.yml
+const COMPONENTS: &[(&str, i32)] = &[
+    ("unknown", 0),
+    ("tomcat", 1),
+    ("httpclient", 2),
+    ("dubbo", 3),
+    ("h2", 4),
+    ("mysql", 5),
+    ("oracle", 6),
+    ("redis", 7),
+    ("motan", 8),
+    ("mongodb", 9),
+    ("memcached", 20),
+    ("postgresql", 22),
+    ("grpc", 23),
+    ("rocketmq", 25),
+    ("kafka", 27),
+    ("rust", 11000),
+];
+
+/// Registry installed for the whole process by `ComponentRegistry::install`.
+static GLOBAL_REGISTRY: OnceCell<ComponentRegistry> = OnceCell::new();
+
+/// Resolve the component ID from its name. Names are case insensitive.
+/// Components of the installed registry take precedence over the built-in ones.
+pub fn component_id(name: &str) -> Option<i32> {
+    GLOBAL_REGISTRY
+        .get()
+        .and_then(|registry| registry.loaded_component_id(name))
+        .or_else(|| builtin_component_id(name))
+}
+
+fn builtin_component_id(name: &str) -> Option<i32> {
+    COMPONENTS
+        .iter()
+        .find(|(component, _)| component.eq_ignore_ascii_case(name))
+        .map(|(_, id)| *id)
+}
+
+/// Key of component-libraries.yml which is not a component.
+const COMPONENT_SERVER_MAPPINGS: &str = "Component-Server-Mappings";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ComponentLoadError {
+    #[error("failed to read component libraries: {0}")]
+    Io(io::Error),
+    #[error("failed to parse component libraries: {0}")]
+    Parse(serde_yaml::Error),
+    /// The component doesn't have a valid `id`.
+    #[error("component {0} must have an integer id.")]
+    InvalidComponent(String),
+}
+
+/// Component IDs loaded from a file compatible with SkyWalking's
+/// component-libraries.yml, falling back to the built-in ones.
+#[derive(Default)]
+pub struct ComponentRegistry {
+    components: HashMap<String, i32>,
+}
+
+impl ComponentRegistry {
+    pub fn from_yaml_file<P: AsRef<Path>>(path: P) -> Result<Self, ComponentLoadError> {
+        let content = fs::read_to_string(path).map_err(ComponentLoadError::Io)?;
+        ComponentRegistry::from_yaml_str(&content)
+    }
+
+    pub fn from_yaml_str(content: &str) -> Result<Self, ComponentLoadError> {
+        let libraries: HashMap<String, Value> =
+            serde_yaml::from_str(content).map_err(ComponentLoadError::Parse)?;
+
+        let mut components = HashMap::new();
+        for (name, library) in libraries {
+            if name == COMPONENT_SERVER_MAPPINGS {
+                continue;
+            }
+            let id = library
+                .get("id")
+                .and_then(|id| id.as_i64())
+                .filter(|id| *id >= 0 && *id <= i32::MAX as i64)
+                .ok_or_else(|| ComponentLoadError::InvalidComponent(name.clone()))?;
+            components.insert(name.to_ascii_lowercase(), id as i32);
+        }
+        Ok(ComponentRegistry { components })
+    }
+
+    /// Resolve the component ID from its name. Names are case insensitive.
+    pub fn component_id(&self, name: &str) -> Option<i32> {
+        self.loaded_component_id(name)
+            .or_else(|| builtin_component_id(name))
+    }
+
+    /// Use this registry for the whole process, so that the free `component_id`
+    /// and span creators like `create_cache_exit_span` resolve the loaded
+    /// components. It can be installed only once, and the registry is given
+    /// back if another one is already installed.
+    pub fn install(self) -> Result<(), ComponentRegistry> {
+        GLOBAL_REGISTRY.set(self)
+    }
+
+    fn loaded_component_id(&self, name: &str) -> Option<i32> {
+        self.components.get(&name.to_ascii_lowercase()).copied()
+    }
+}
@@ -14,5 +14,7 @@
 // limitations under the License.
 //
 
+pub mod components;
+pub mod peer;
 pub mod random_generator;
 pub mod time;
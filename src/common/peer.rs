@@ -0,0 +1,65 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+/// Split a remote peer into its host and port.
+/// Peers are treated as opaque addresses by SkyWalking, so this never rewrites
+/// the host. Unix domain sockets like `unix:/var/run/app.sock` and IPv6 literals
+/// without brackets don't have a port. For bracketed IPv6 literals like
+/// `[2001:db8::1]:443`, the brackets are removed from the host.
+pub fn split_peer(peer: &str) -> (&str, Option<u16>) {
+    if peer.starts_with("unix:") {
+        return (peer, None);
+    }
+
+    if let Some(rest) = peer.strip_prefix('[') {
+        return match rest.split_once(']') {
+            Some((host, tail)) => match tail.strip_prefix(':') {
+                Some(port) => (host, port.parse::<u16>().ok()),
+                None => (host, None),
+            },
+            None => (peer, None),
+        };
+    }
+
+    match peer.rsplit_once(':') {
+        // More than one colon means an IPv6 literal without brackets.
+        Some((host, port)) if !host.contains(':') => match port.parse::<u16>() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (peer, None),
+        },
+        _ => (peer, None),
+    }
+}
+
+/// Extract the authority, i.e. `host:port`, from `url` like
+/// `http://consumer:8082/pong`. The port is kept only if it is in the URL.
+/// Userinfo is removed. It returns `None` if `url` doesn't have a scheme or host.
+pub fn authority_from_url(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest
+        .split(|c| c == '/' || c == '?' || c == '#')
+        .next()
+        .unwrap_or_default();
+    let authority = match authority.rsplit_once('@') {
+        Some((_, host)) => host,
+        None => authority,
+    };
+
+    if authority.is_empty() {
+        return None;
+    }
+    Some(authority.to_string())
+}
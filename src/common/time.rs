@@ -14,7 +14,63 @@
 // limitations under the License.
 //
 
+use std::sync::Arc;
+
+/// Source of the time of spans. All the values are in milliseconds, which OAP
+/// expects, except the `_nanos` ones. `UnixTimeStampFetcher` returned seconds
+/// before, so implementations written for that unit must be updated.
 pub trait TimeFetcher {
-    // Get current UNIX timestamp with sec resolution.
+    // Get current UNIX timestamp with millisecond resolution.
     fn get(&self) -> i64;
+
+    // Get elapsed time from an arbitrary fixed point with the same resolution as `get()`.
+    // It must never go backward, so it is used to measure durations of spans.
+    fn get_monotonic(&self) -> i64 {
+        self.get()
+    }
+
+    // Get current UNIX timestamp with nanosecond resolution, for exporters which
+    // need finer resolution than OAP, like OTLP. It is derived from `get()` by default.
+    fn get_nanos(&self) -> i128 {
+        self.get() as i128 * NANOS_PER_MILLI
+    }
+
+    // Get elapsed time from the same fixed point as `get_monotonic()` with nanosecond
+    // resolution. It is derived from `get_monotonic()` by default.
+    fn get_monotonic_nanos(&self) -> i128 {
+        self.get_monotonic() as i128 * NANOS_PER_MILLI
+    }
+}
+
+pub const NANOS_PER_MILLI: i128 = 1_000_000;
+
+/// Time fetcher which shifts the wall clock of `inner` by `offset_ms`, to correct
+/// the clock skew against the collector. The monotonic clock is not shifted.
+pub struct OffsetTimeFetcher {
+    inner: Arc<dyn TimeFetcher + Sync + Send>,
+    offset_ms: i64,
+}
+
+impl OffsetTimeFetcher {
+    pub fn new(inner: Arc<dyn TimeFetcher + Sync + Send>, offset_ms: i64) -> Self {
+        OffsetTimeFetcher { inner, offset_ms }
+    }
+}
+
+impl TimeFetcher for OffsetTimeFetcher {
+    fn get(&self) -> i64 {
+        self.inner.get() + self.offset_ms
+    }
+
+    fn get_monotonic(&self) -> i64 {
+        self.inner.get_monotonic()
+    }
+
+    fn get_nanos(&self) -> i128 {
+        self.inner.get_nanos() + self.offset_ms as i128 * NANOS_PER_MILLI
+    }
+
+    fn get_monotonic_nanos(&self) -> i128 {
+        self.inner.get_monotonic_nanos()
+    }
 }
@@ -0,0 +1,67 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::context::trace_context::TracingContext;
+use std::ops::{Deref, DerefMut};
+use tokio::sync::mpsc;
+
+/// Tracing context which is sent to the reporter when it is dropped.
+/// Spans created with guards, like `entry_guard()`, are finalized before that
+/// because they borrow the context. Spans which are still active then, like the
+/// ones created with `create_*_span` and never finalized, are finalized from
+/// the innermost one with the data recorded when they were created.
+pub struct ManagedContext {
+    context: Option<TracingContext>,
+    sender: mpsc::Sender<TracingContext>,
+}
+
+impl ManagedContext {
+    /// `sender` is typically the one returned by `reporter::grpc::Reporter::start`.
+    pub fn new(mut context: TracingContext, sender: mpsc::Sender<TracingContext>) -> Self {
+        context.set_track_open_spans(true);
+        ManagedContext {
+            context: Some(context),
+            sender,
+        }
+    }
+}
+
+impl Deref for ManagedContext {
+    type Target = TracingContext;
+
+    fn deref(&self) -> &TracingContext {
+        self.context.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for ManagedContext {
+    fn deref_mut(&mut self) -> &mut TracingContext {
+        self.context.as_mut().unwrap()
+    }
+}
+
+impl Drop for ManagedContext {
+    fn drop(&mut self) {
+        if let Some(mut context) = self.context.take() {
+            context.finalize_active_spans();
+            if !context.should_report() {
+                return;
+            }
+            // It can't wait in drop, so the context is dropped if the queue is full.
+            let _ = self.sender.try_send(context);
+        }
+    }
+}
@@ -14,6 +14,9 @@
 // limitations under the License.
 //
 
+pub mod managed_context;
 pub mod propagation;
 pub mod system_time;
 pub mod trace_context;
+#[cfg(feature = "tracing")]
+pub mod tracing_ext;
@@ -0,0 +1,103 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::context::propagation::context::PropagationContext;
+use crate::error::DecodeError;
+
+/// Headers of B3 multi-header propagation.
+pub const B3_TRACE_ID_HEADER_KEY: &str = "X-B3-TraceId";
+pub const B3_SPAN_ID_HEADER_KEY: &str = "X-B3-SpanId";
+pub const B3_PARENT_SPAN_ID_HEADER_KEY: &str = "X-B3-ParentSpanId";
+pub const B3_SAMPLED_HEADER_KEY: &str = "X-B3-Sampled";
+
+/// Header of B3 single-header propagation.
+pub const B3_SINGLE_HEADER_KEY: &str = "b3";
+
+/// Decode B3 multi headers into the propagation context, for interoperability
+/// with the edges which only speak B3. B3 span IDs are 64 bit, so the span ID
+/// and the parent span ID are kept as `b3_span_id` and `b3_parent_span_id`.
+/// B3 has no segments, so the parent segment ID is left empty as well as
+/// service, instance, endpoint and address.
+pub fn decode_b3(
+    trace_id: &str,
+    span_id: &str,
+    parent_span_id: Option<&str>,
+    sampled: Option<&str>,
+) -> Result<PropagationContext, DecodeError> {
+    if !is_hex(trace_id, &[16, 32]) {
+        return Err(DecodeError("failed to parse b3 trace id."));
+    }
+    if !is_hex(span_id, &[16]) {
+        return Err(DecodeError("failed to parse b3 span id."));
+    }
+    if let Some(parent_span_id) = parent_span_id {
+        if !is_hex(parent_span_id, &[16]) {
+            return Err(DecodeError("failed to parse b3 parent span id."));
+        }
+    }
+    let do_sample = match sampled {
+        Some(sampled) => try_parse_b3_sampled(sampled)?,
+        // The decision is deferred to the receiver.
+        None => true,
+    };
+
+    let mut context = sampling_only_context(do_sample);
+    context.parent_trace_id = trace_id.to_ascii_lowercase();
+    context.b3_span_id = Some(span_id.to_ascii_lowercase());
+    context.b3_parent_span_id = parent_span_id.map(|id| id.to_ascii_lowercase());
+    Ok(context)
+}
+
+/// Decode the `b3` single header, `{TraceId}-{SpanId}-{SamplingState}-{ParentSpanId}`
+/// where the last two are optional. The header may only have the sampling
+/// state, like `0` or `d`. It has no IDs then, so only `do_sample` is decoded
+/// and the trace can't be continued.
+pub fn decode_b3_single(header_value: &str) -> Result<PropagationContext, DecodeError> {
+    let pieces: Vec<&str> = header_value.trim().split('-').collect();
+
+    match pieces.len() {
+        1 => Ok(sampling_only_context(try_parse_b3_sampled(pieces[0])?)),
+        2 => decode_b3(pieces[0], pieces[1], None, None),
+        3 => decode_b3(pieces[0], pieces[1], None, Some(pieces[2])),
+        4 => decode_b3(pieces[0], pieces[1], Some(pieces[3]), Some(pieces[2])),
+        _ => Err(DecodeError("failed to parse b3 header: too many fields.")),
+    }
+}
+
+fn sampling_only_context(do_sample: bool) -> PropagationContext {
+    PropagationContext::new(
+        do_sample,
+        String::default(),
+        String::default(),
+        0,
+        String::default(),
+        String::default(),
+        String::default(),
+        String::default(),
+    )
+}
+
+fn try_parse_b3_sampled(sampled: &str) -> Result<bool, DecodeError> {
+    match sampled {
+        "1" | "true" | "d" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(DecodeError("failed to parse b3 sample status.")),
+    }
+}
+
+fn is_hex(id: &str, lengths: &[usize]) -> bool {
+    lengths.contains(&id.len()) && id.chars().all(|c| c.is_ascii_hexdigit())
+}
@@ -14,6 +14,13 @@
 // limitations under the License.
 //
 
+/// Header which carries the propagated context.
+pub const SKYWALKING_HTTP_CONTEXT_HEADER_KEY: &str = "sw8";
+
+/// Header which carries the extension flags of the propagated context.
+pub const SKYWALKING_HTTP_EXTENSION_HEADER_KEY: &str = "sw8-x";
+
+#[derive(Clone, Debug)]
 pub struct PropagationContext {
     /// It defines whether next span should be trace or not.
     /// In SkyWalking, If `do_sample == true`, the span should be reported to
@@ -35,11 +42,26 @@ pub struct PropagationContext {
     /// Instance name of service parent belongs.
     pub parent_service_instance: String,
 
-    /// An endpoint name that parent requested to.
+    /// Entry endpoint of the parent segment, or the endpoint which parent
+    /// requested to if the parent has no entry span.
     pub destination_endpoint: String,
 
     /// An address that parent requested to. It can be authority or network address.
     pub destination_address: String,
+
+    /// Whether the parent reached the limit of spans. It is carried by `sw8-x`.
+    pub span_limit_reached: bool,
+
+    /// Whether OAP should skip the analysis of the downstream spans. It is
+    /// carried by `sw8-x`.
+    pub skip_analysis: bool,
+
+    /// Span ID of the caller which is propagated by B3, in hex. B3 span IDs are
+    /// 64 bit, so it can't be expressed by `parent_span_id`.
+    pub b3_span_id: Option<String>,
+
+    /// Parent span ID of the caller which is propagated by B3, in hex.
+    pub b3_parent_span_id: Option<String>,
 }
 
 impl PropagationContext {
@@ -63,6 +85,42 @@ impl PropagationContext {
             parent_service_instance,
             destination_endpoint,
             destination_address,
+            span_limit_reached: false,
+            skip_analysis: false,
+            b3_span_id: None,
+            b3_parent_span_id: None,
         }
     }
+
+    pub fn is_sampled(&self) -> bool {
+        self.do_sample
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.parent_trace_id
+    }
+
+    pub fn segment_id(&self) -> &str {
+        &self.parent_trace_segment_id
+    }
+
+    pub fn span_id(&self) -> i32 {
+        self.parent_span_id
+    }
+
+    pub fn service(&self) -> &str {
+        &self.parent_service
+    }
+
+    pub fn service_instance(&self) -> &str {
+        &self.parent_service_instance
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.destination_endpoint
+    }
+
+    pub fn address(&self) -> &str {
+        &self.destination_address
+    }
 }
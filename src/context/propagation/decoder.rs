@@ -15,22 +15,98 @@
 //
 
 use crate::context::propagation::context::PropagationContext;
+use crate::error::DecodeError;
 use base64::decode;
+use std::convert::TryFrom;
 
-pub fn decode_propagation(header_value: &str) -> Result<PropagationContext, &str> {
-    let pieces: Vec<&str> = header_value.split('-').collect();
+/// Options to decode headers which were modified in transit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecodeOptions {
+    /// Replace invalid UTF-8 in the display fields with U+FFFD.
+    /// See `decode_propagation_lenient`.
+    pub lenient_utf8: bool,
+    /// Percent-decode the header value before decoding base64, for frameworks
+    /// which URL-encode `+`, `/` and `=` of base64.
+    pub percent_decode: bool,
+    /// Accept more than 8 fields and ignore the extra ones, for the headers
+    /// of future revisions of `sw8` which may append fields.
+    pub allow_extra_fields: bool,
+}
+
+pub fn decode_propagation(header_value: &str) -> Result<PropagationContext, DecodeError> {
+    decode_propagation_with_options(header_value, &DecodeOptions::default())
+}
+
+/// Decode the header which may be legitimately absent. It returns `None` if the
+/// header is absent or empty, and logs the error and returns `None` if it is
+/// invalid, so that the handler can simply start a new trace.
+pub fn try_decode_propagation(header_value: Option<&str>) -> Option<PropagationContext> {
+    let header_value = header_value?;
+    if header_value.trim().is_empty() {
+        return None;
+    }
+
+    match decode_propagation(header_value) {
+        Ok(context) => Some(context),
+        Err(e) => {
+            log::warn!("ignore invalid sw8 header: {}", e);
+            None
+        }
+    }
+}
 
-    if pieces.len() != 8 {
-        return Err("failed to parse propagation context: it must have 8 properties.");
+/// Same as `decode_propagation`, but invalid UTF-8 in the parent service, instance
+/// and endpoint is replaced with U+FFFD instead of failing. These fields are only
+/// for display, so a corrupted byte in them doesn't lose the trace linkage.
+pub fn decode_propagation_lenient(header_value: &str) -> Result<PropagationContext, DecodeError> {
+    let options = DecodeOptions {
+        lenient_utf8: true,
+        ..Default::default()
+    };
+    decode_propagation_with_options(header_value, &options)
+}
+
+pub fn decode_propagation_with_options(
+    header_value: &str,
+    options: &DecodeOptions,
+) -> Result<PropagationContext, DecodeError> {
+    let decoded;
+    let header_value = if options.percent_decode {
+        decoded = percent_decode(header_value)?;
+        decoded.as_str()
+    } else {
+        header_value
+    };
+
+    // Some HTTP stacks join duplicated headers with commas. Comma never appears in
+    // a valid value, so the first one is used.
+    let header_value = header_value.split(',').next().unwrap_or_default();
+    // Proxies may add whitespaces or CR around the value. They never appear in
+    // a valid value, so they are trimmed.
+    let pieces: Vec<&str> = header_value
+        .trim()
+        .split('-')
+        .map(|piece| piece.trim())
+        .collect();
+
+    if pieces.len() < 8 || (pieces.len() > 8 && !options.allow_extra_fields) {
+        return Err(DecodeError(
+            "failed to parse propagation context: it must have 8 properties.",
+        ));
     }
 
     let do_sample = try_parse_sample_status(pieces[0])?;
     let parent_trace_id = b64_encoded_into_string(pieces[1])?;
     let parent_trace_segment_id = b64_encoded_into_string(pieces[2])?;
     let parent_span_id: i32 = try_parse_parent_span_id(pieces[3])?;
-    let parent_service = b64_encoded_into_string(pieces[4])?;
-    let parent_service_instance = b64_encoded_into_string(pieces[5])?;
-    let destination_endpoint = b64_encoded_into_string(pieces[6])?;
+    let decode_display_field = if options.lenient_utf8 {
+        b64_encoded_into_string_lossy
+    } else {
+        b64_encoded_into_string
+    };
+    let parent_service = decode_display_field(pieces[4])?;
+    let parent_service_instance = decode_display_field(pieces[5])?;
+    let destination_endpoint = decode_display_field(pieces[6])?;
     let destination_address = b64_encoded_into_string(pieces[7])?;
 
     let context = PropagationContext::new(
@@ -47,30 +123,101 @@ pub fn decode_propagation(header_value: &str) -> Result<PropagationContext, &str
     Ok(context)
 }
 
-fn try_parse_parent_span_id(id: &str) -> Result<i32, &str> {
-    if let Ok(result) = id.parse::<i32>() {
-        Ok(result)
-    } else {
-        Err("failed to parse span id from parent.")
+/// Decode `sw8-x` header, which consists of the span limit flag and the skip
+/// analysis flag, into `context`.
+pub fn decode_extension(
+    context: &mut PropagationContext,
+    header_value: &str,
+) -> Result<(), DecodeError> {
+    let pieces: Vec<&str> = header_value.trim().split('-').map(str::trim).collect();
+    if pieces.len() != 2 {
+        return Err(DecodeError(
+            "failed to parse extension: it must have 2 properties.",
+        ));
     }
+
+    context.span_limit_reached = try_parse_extension_flag(pieces[0])?;
+    context.skip_analysis = try_parse_extension_flag(pieces[1])?;
+    Ok(())
 }
 
-fn try_parse_sample_status(status: &str) -> Result<bool, &str> {
+fn try_parse_extension_flag(flag: &str) -> Result<bool, DecodeError> {
+    match flag {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(DecodeError("failed to parse extension flag.")),
+    }
+}
+
+fn try_parse_parent_span_id(id: &str) -> Result<i32, DecodeError> {
+    let result = match id.parse::<i64>() {
+        Ok(result) => result,
+        Err(_) => return Err(DecodeError("failed to parse span id from parent.")),
+    };
+
+    // Span IDs are small numbers counted in every segment, so a value beyond
+    // `i32` means the header is corrupted. A negative value can't be in the
+    // header, since `-` separates the fields.
+    i32::try_from(result).map_err(|_| DecodeError("span id from parent is out of range."))
+}
+
+fn try_parse_sample_status(status: &str) -> Result<bool, DecodeError> {
     if status == "0" {
         Ok(false)
     } else if status == "1" {
         Ok(true)
     } else {
-        Err("failed to parse sample status.")
+        Err(DecodeError("failed to parse sample status."))
     }
 }
 
-fn b64_encoded_into_string(enc: &str) -> Result<String, &str> {
+// An empty field is decoded into an empty string explicitly, since some agents
+// leave unknown fields empty.
+fn b64_encoded_into_string(enc: &str) -> Result<String, DecodeError> {
+    if enc.is_empty() {
+        return Ok(String::new());
+    }
     if let Ok(result) = decode(enc) {
         if let Ok(decoded_str) = String::from_utf8(result) {
             return Ok(decoded_str);
         }
     }
 
-    Err("failed to decode value.")
+    Err(DecodeError("failed to decode value."))
+}
+
+fn b64_encoded_into_string_lossy(enc: &str) -> Result<String, DecodeError> {
+    if enc.is_empty() {
+        return Ok(String::new());
+    }
+    if let Ok(result) = decode(enc) {
+        return Ok(String::from_utf8_lossy(&result).into_owned());
+    }
+
+    Err(DecodeError("failed to decode value."))
+}
+
+/// Decode `%XX` escapes. `+` is kept, since it is a valid character of base64.
+fn percent_decode(value: &str) -> Result<String, DecodeError> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let escaped = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => decoded.push(byte),
+                None => return Err(DecodeError("failed to percent-decode value.")),
+            }
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| DecodeError("failed to percent-decode value."))
 }
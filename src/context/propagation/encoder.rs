@@ -17,16 +17,58 @@
 use crate::context::trace_context::TracingContext;
 use base64::encode;
 
-pub fn encode_propagation(context: &TracingContext, endpoint: &str, address: &str) -> String {
+/// Value encoded instead of the destination endpoint or address which is unknown.
+/// Some decoders reject empty fields, so it is never encoded as an empty string.
+pub const UNKNOWN_DESTINATION: &str = "N/A";
+
+/// Encode the extension flags of `context` into sw8-x header value. The flags
+/// propagated from the parent are passed through to the downstream.
+pub fn encode_extension(context: &TracingContext) -> String {
+    let flag = |enabled: bool| if enabled { "1" } else { "0" };
+    format!(
+        "{}-{}",
+        flag(context.is_span_limit_reached()),
+        flag(context.is_skip_analysis())
+    )
+}
+
+/// Encode the context into sw8 header value. The parent endpoint is the entry
+/// endpoint of `context`, so that the downstream's reference points the
+/// operation which started this segment. `fallback_endpoint` is used only if
+/// the context has no entry span.
+pub fn encode_propagation(
+    context: &TracingContext,
+    fallback_endpoint: Option<&str>,
+    address: Option<&str>,
+) -> String {
     let mut res = String::new();
 
-    res += "1-";
+    if context.is_sampled() {
+        res += "1-";
+    } else {
+        res += "0-";
+    }
     res += format!("{}-", encode(context.trace_id.to_string())).as_str();
     res += format!("{}-", encode(context.trace_segment_id.to_string())).as_str();
-    res += format!("{}-", context.next_span_id.to_string()).as_str();
-    res += format!("{}-", encode(context.service.as_str())).as_str();
+    // The active span, usually the exit span, is the parent, as in
+    // `make_downstream_context`. Span ID is never negative, since `-` is the
+    // separator.
+    let span_id = context
+        .active_span_id()
+        .unwrap_or_else(|| (context.next_span_id - 1).max(0));
+    res += format!("{}-", span_id).as_str();
+    res += format!("{}-", encode(context.namespaced_service())).as_str();
     res += format!("{}-", encode(context.service_instance.as_str())).as_str();
-    res += format!("{}-", encode(endpoint)).as_str();
-    res += &encode(address);
+    res += format!(
+        "{}-",
+        encode(
+            context
+                .entry_endpoint()
+                .or(fallback_endpoint)
+                .unwrap_or(UNKNOWN_DESTINATION)
+        )
+    )
+    .as_str();
+    res += &encode(address.unwrap_or(UNKNOWN_DESTINATION));
     res
 }
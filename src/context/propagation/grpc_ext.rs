@@ -0,0 +1,51 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::context::propagation::context::{
+    PropagationContext, SKYWALKING_HTTP_CONTEXT_HEADER_KEY,
+};
+use crate::context::propagation::decoder::decode_propagation;
+use crate::context::propagation::encoder::encode_propagation;
+use crate::context::trace_context::TracingContext;
+use crate::error::{DecodeError, Result};
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+/// Decode `sw8` of the gRPC request metadata. It returns `None` if it is absent,
+/// and the decode error if it is invalid.
+pub fn extract_from_metadata(metadata: &MetadataMap) -> Option<Result<PropagationContext>> {
+    let value = metadata.get(SKYWALKING_HTTP_CONTEXT_HEADER_KEY)?;
+    let context = match value.to_str() {
+        Ok(value) => decode_propagation(value),
+        Err(_) => Err(DecodeError("sw8 metadata must be ASCII.")),
+    };
+    Some(context.map_err(Into::into))
+}
+
+/// Encode the context into `sw8` of the metadata of the outgoing gRPC request.
+/// `address` is the destination of this request. The parent endpoint is the
+/// entry endpoint of `context`, and `fallback_endpoint` is used only if the
+/// context has no entry span.
+pub fn inject_into_metadata(
+    metadata: &mut MetadataMap,
+    context: &TracingContext,
+    fallback_endpoint: &str,
+    address: &str,
+) {
+    let header = encode_propagation(context, Some(fallback_endpoint), Some(address));
+    if let Ok(value) = MetadataValue::from_str(&header) {
+        metadata.insert(SKYWALKING_HTTP_CONTEXT_HEADER_KEY, value);
+    }
+}
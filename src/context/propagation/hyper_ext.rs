@@ -0,0 +1,54 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::context::propagation::context::{
+    PropagationContext, SKYWALKING_HTTP_CONTEXT_HEADER_KEY,
+};
+use crate::context::propagation::decoder::decode_propagation;
+use crate::context::propagation::encoder::encode_propagation;
+use crate::context::trace_context::TracingContext;
+use hyper::header::HeaderValue;
+use hyper::Request;
+
+/// Helpers to propagate context through `sw8` header of hyper requests.
+pub trait RequestTracingExt {
+    /// Decode the `sw8` header. It returns `None` if the header is absent or invalid.
+    fn extract_sw8(&self) -> Option<PropagationContext>;
+
+    /// Encode the context into `sw8` header. `address` is the destination of
+    /// this request. The parent endpoint is the entry endpoint of `context`, and
+    /// `fallback_endpoint` is used only if the context has no entry span.
+    fn inject_sw8(&mut self, context: &TracingContext, fallback_endpoint: &str, address: &str);
+}
+
+impl<B> RequestTracingExt for Request<B> {
+    fn extract_sw8(&self) -> Option<PropagationContext> {
+        let value = self
+            .headers()
+            .get(SKYWALKING_HTTP_CONTEXT_HEADER_KEY)?
+            .to_str()
+            .ok()?;
+        decode_propagation(value).ok()
+    }
+
+    fn inject_sw8(&mut self, context: &TracingContext, fallback_endpoint: &str, address: &str) {
+        let header = encode_propagation(context, Some(fallback_endpoint), Some(address));
+        if let Ok(value) = HeaderValue::from_str(&header) {
+            self.headers_mut()
+                .insert(SKYWALKING_HTTP_CONTEXT_HEADER_KEY, value);
+        }
+    }
+}
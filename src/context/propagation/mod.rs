@@ -14,6 +14,11 @@
 // limitations under the License.
 //
 
+pub mod b3;
 pub mod context;
 pub mod decoder;
 pub mod encoder;
+#[cfg(feature = "grpc")]
+pub mod grpc_ext;
+#[cfg(feature = "hyper")]
+pub mod hyper_ext;
@@ -15,16 +15,41 @@
 //
 
 use crate::common::time::TimeFetcher;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Default)]
-pub struct UnixTimeStampFetcher {}
+/// Time fetcher of the system clock, in milliseconds since the UNIX epoch.
+pub struct UnixTimeStampFetcher {
+    anchor: Instant,
+}
+
+impl Default for UnixTimeStampFetcher {
+    fn default() -> Self {
+        UnixTimeStampFetcher {
+            anchor: Instant::now(),
+        }
+    }
+}
 
 impl TimeFetcher for UnixTimeStampFetcher {
     fn get(&self) -> i64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_secs() as i64
+            .as_millis() as i64
+    }
+
+    fn get_monotonic(&self) -> i64 {
+        self.anchor.elapsed().as_millis() as i64
+    }
+
+    fn get_nanos(&self) -> i128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i128
+    }
+
+    fn get_monotonic_nanos(&self) -> i128 {
+        self.anchor.elapsed().as_nanos() as i128
     }
 }
@@ -14,24 +14,45 @@
 // limitations under the License.
 //
 
+use crate::common::components::component_id;
+use crate::common::peer::{authority_from_url, split_peer};
 use crate::common::random_generator::RandomGenerator;
-use crate::common::time::TimeFetcher;
+use crate::common::time::{OffsetTimeFetcher, TimeFetcher, NANOS_PER_MILLI};
 use crate::context::propagation::context::PropagationContext;
+use crate::context::propagation::decoder::decode_propagation;
+use crate::error::{Error, Result};
+use crate::reporter::reporter_trait::{MeterReporter, Reporter};
 use crate::skywalking_proto::v3::{
     KeyStringValuePair, Log, RefType, SegmentObject, SegmentReference, SpanLayer, SpanObject,
     SpanType,
 };
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use super::system_time::UnixTimeStampFetcher;
 
 pub struct Span {
     span_internal: SpanObject,
+    start_monotonic: i64,
+    start_monotonic_nanos: i128,
+    start_nanos: i128,
+    end_nanos: i128,
     time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+    noop: bool,
+    deadline: Option<i64>,
+    closed: bool,
+    max_references: Option<usize>,
+    priority: Option<Arc<AtomicBool>>,
+    kept: bool,
+    dropped: bool,
 }
 
 impl Span {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        span_id: i32,
         parent_span_id: i32,
         operation_name: String,
         remote_peer: String,
@@ -40,41 +61,165 @@ impl Span {
         skip_analysis: bool,
         time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
     ) -> Self {
+        // Only the fields which are known here are set. The others, like `end_time`,
+        // `refs`, `tags` and `logs`, take their proto default values so that
+        // new fields added into `SpanObject` don't break span construction.
         let span_internal = SpanObject {
-            span_id: parent_span_id + 1,
+            span_id,
             parent_span_id,
             start_time: time_fetcher.get(),
-            end_time: 0, // not set
-            refs: Vec::<SegmentReference>::new(),
             operation_name,
             peer: remote_peer,
             span_type: span_type as i32,
             span_layer: span_layer as i32,
-            // TODO(shikugawa): define this value in
-            // https://github.com/apache/skywalking/blob/6452e0c2d983c85c392602d50436e8d8e421fec9/oap-server/server-starter/src/main/resources/component-libraries.yml
-            component_id: 11000,
-            is_error: false,
-            tags: Vec::<KeyStringValuePair>::new(),
-            logs: Vec::<Log>::new(),
+            component_id: DEFAULT_COMPONENT_ID,
             skip_analysis,
+            ..Default::default()
         };
 
         Span {
             span_internal,
+            start_monotonic: time_fetcher.get_monotonic(),
+            start_monotonic_nanos: time_fetcher.get_monotonic_nanos(),
+            start_nanos: time_fetcher.get_nanos(),
+            end_nanos: 0,
             time_fetcher,
+            noop: false,
+            deadline: None,
+            closed: false,
+            max_references: None,
+            priority: None,
+            kept: false,
+            dropped: false,
         }
     }
 
+    /// Create a span which discards everything recorded on it. It is used by
+    /// contexts which are not sampled, so it doesn't allocate any span data.
+    /// Only the start time is recorded, and the context sets the identity of
+    /// the span, so that it can be kept by `set_sampling_priority`.
+    pub fn noop(time_fetcher: Arc<dyn TimeFetcher + Sync + Send>) -> Self {
+        let span_internal = SpanObject {
+            start_time: time_fetcher.get(),
+            ..Default::default()
+        };
+        Span {
+            span_internal,
+            start_monotonic: time_fetcher.get_monotonic(),
+            start_monotonic_nanos: time_fetcher.get_monotonic_nanos(),
+            start_nanos: time_fetcher.get_nanos(),
+            end_nanos: 0,
+            time_fetcher,
+            noop: true,
+            deadline: None,
+            closed: false,
+            max_references: None,
+            priority: None,
+            kept: false,
+            dropped: false,
+        }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.noop
+    }
+
+    /// Copy of the open span as it is now, which the context keeps to finalize
+    /// the span if the owner never does.
+    fn open_copy(&self) -> Box<Span> {
+        Box::new(Span {
+            span_internal: self.span_internal.clone(),
+            start_monotonic: self.start_monotonic,
+            start_monotonic_nanos: self.start_monotonic_nanos,
+            start_nanos: self.start_nanos,
+            end_nanos: 0,
+            time_fetcher: self.time_fetcher.clone(),
+            noop: self.noop,
+            deadline: None,
+            closed: false,
+            max_references: self.max_references,
+            priority: self.priority.clone(),
+            kept: self.kept,
+            dropped: self.dropped,
+        })
+    }
+
     // TODO(shikugawa): not to call `close()` explicitly.
+    /// Record the end time of the span. Only the first call takes effect, so a
+    /// span closed by hand is not extended when it is finalized later.
     pub fn close(&mut self) {
-        self.span_internal.end_time = self.time_fetcher.get();
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        // `start_time` is wall clock time for OAP, but the duration is measured
+        // with the monotonic clock not to be affected by clock adjustments.
+        let elapsed = self.time_fetcher.get_monotonic() - self.start_monotonic;
+        self.span_internal.end_time = self.span_internal.start_time + elapsed;
+        let elapsed_nanos = self.time_fetcher.get_monotonic_nanos() - self.start_monotonic_nanos;
+        self.end_nanos = self.start_nanos + elapsed_nanos;
+    }
+
+    /// Start time in nanoseconds, for exporters which need finer resolution
+    /// than `start_time` of the span object, like OTLP.
+    pub fn start_time_nanos(&self) -> i128 {
+        self.start_nanos
+    }
+
+    /// End time in nanoseconds. It is 0 until the span is closed.
+    pub fn end_time_nanos(&self) -> i128 {
+        self.end_nanos
+    }
+
+    /// Set the end time in milliseconds, without finer resolution.
+    fn set_end_time(&mut self, end_time: i64) {
+        self.span_internal.end_time = end_time;
+        self.end_nanos = end_time as i128 * NANOS_PER_MILLI;
+    }
+
+    /// Give up the operation if it doesn't finish within `timeout` since the span
+    /// started. Only detached spans are finalized by the sweeper on the deadline.
+    /// See `TracingContext::detach_span`.
+    pub fn set_deadline(&mut self, timeout: Duration) {
+        self.deadline = Some(self.start_monotonic + timeout.as_millis() as i64);
+    }
+
+    fn is_expired(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => self.time_fetcher.get_monotonic() >= deadline,
+            None => false,
+        }
     }
 
     pub fn span_object(&self) -> &SpanObject {
         &self.span_internal
     }
 
+    /// Tags which have been recorded on this span.
+    pub fn tags(&self) -> &[KeyStringValuePair] {
+        &self.span_internal.tags
+    }
+
+    /// Value of the tag `key`. If the key is repeated, the first value is returned.
+    pub fn get_tag(&self, key: &str) -> Option<&str> {
+        self.span_internal
+            .tags
+            .iter()
+            .find(|tag| tag.key == key)
+            .map(|tag| tag.value.as_str())
+    }
+
+    /// Logs which have been recorded on this span.
+    pub fn logs(&self) -> &[Log] {
+        &self.span_internal.logs
+    }
+
     pub fn add_log(&mut self, message: Vec<(&str, &str)>) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+
         let log = Log {
             time: self.time_fetcher.get(),
             data: message
@@ -91,7 +236,110 @@ impl Span {
         self.span_internal.logs.push(log);
     }
 
+    /// Add a log which consists of all key-values of `fields`, like the entries of
+    /// `HashMap<String, String>`. If a key is repeated, the last value wins.
+    pub fn add_logs<I: IntoIterator<Item = (String, String)>>(&mut self, fields: I) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+
+        let mut data = Vec::<KeyStringValuePair>::new();
+        merge_key_values(&mut data, fields);
+        self.span_internal.logs.push(Log {
+            time: self.time_fetcher.get(),
+            data,
+        });
+    }
+
+    /// Rename the operation. It is useful when the operation name, like a route
+    /// template, is known only after the span was created.
+    pub fn set_operation_name(&mut self, name: String) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+        self.span_internal.operation_name = name;
+    }
+
+    /// Set the operation name of the entry span, which OAP uses as the endpoint.
+    /// The query string and the fragment are stripped, since they explode the
+    /// cardinality of endpoints.
+    pub fn set_endpoint_name(&mut self, name: &str) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+        self.span_internal.operation_name = strip_query(name).to_string();
+    }
+
+    /// Override the peer. OAP keys the virtual node of databases and caches with
+    /// the peer, so a stable name can be used instead of the connection address.
+    pub fn set_peer(&mut self, peer: &str) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+        self.span_internal.peer = peer.to_string();
+    }
+
+    /// Mark this span as errored, log `err` with the standard keys, `event`,
+    /// `message` and `stack`, and tag it with `error=true`. The stack consists
+    /// of the chain of `source()` of the error.
+    pub fn record_error(&mut self, err: &dyn std::error::Error) {
+        self.set_error(true);
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+
+        let message = err.to_string();
+        let mut stack = message.clone();
+        let mut source = err.source();
+        while let Some(cause) = source {
+            stack += &format!("\ncaused by: {}", cause);
+            source = cause.source();
+        }
+
+        self.add_log(vec![
+            ("event", "error"),
+            ("message", &message),
+            ("stack", &stack),
+        ]);
+        self.add_tag(("error", "true"));
+    }
+
+    /// Set the component ID which is defined in component-libraries.yml.
+    /// `common::components::component_id` resolves it from the name.
+    pub fn set_component_id(&mut self, component_id: i32) {
+        self.span_internal.component_id = component_id;
+    }
+
+    /// Mark this span as errored. OAP shows the trace as errored if any span
+    /// has this flag.
+    pub fn set_error(&mut self, is_error: bool) {
+        self.span_internal.is_error = is_error;
+    }
+
+    /// Tag the gRPC status code of the call as `rpc.status_code`. The span is
+    /// marked as errored unless the code is OK (0).
+    pub fn tag_grpc_status(&mut self, code: i32) {
+        if self.noop {
+            self.drop_data();
+        } else {
+            self.add_tag((RPC_STATUS_CODE_TAG, &code.to_string()));
+        }
+        if code != 0 {
+            self.set_error(true);
+        }
+    }
+
     pub fn add_tag(&mut self, tag: (&str, &str)) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+
         let (key, value) = tag;
         self.span_internal.tags.push(KeyStringValuePair {
             key: key.to_string(),
@@ -99,11 +347,305 @@ impl Span {
         });
     }
 
+    /// Add all tags of `tags`, like the entries of `HashMap<String, String>`.
+    /// If a key is repeated or already set on this span, the last value wins.
+    pub fn add_tags<I: IntoIterator<Item = (String, String)>>(&mut self, tags: I) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+
+        merge_key_values(&mut self.span_internal.tags, tags);
+    }
+
+    /// Record the location of the code which created this span as
+    /// `source.location` tag, like `src/main.rs:42`. `entry_span!` sets it
+    /// automatically.
+    pub fn set_source_location(&mut self, file: &str, line: u32) {
+        if self.noop {
+            self.drop_data();
+            return;
+        }
+        self.add_tag((SOURCE_LOCATION_TAG, &format!("{}:{}", file, line)));
+    }
+
+    /// Add the reference to the parent span described by `link` of the trace
+    /// `trace_id`, e.g. to import references from external data. `ref_type`
+    /// tells whether the parent is in another process or another thread.
+    pub fn add_reference(&mut self, trace_id: &str, link: &PropagationContext, ref_type: RefType) {
+        self.add_segment_reference(segment_reference(trace_id, link, ref_type));
+    }
+
+    /// Force the context which created this span to be reported even if it is
+    /// not sampled, for important operations like payments. It is usually set on
+    /// the entry span right after it is created. Spans of an unsampled context
+    /// are no-op spans which only keep their identity, so nothing is allocated
+    /// for them. This span and the spans created after it are recorded, and the
+    /// entry span gets its operation name and references back from the context
+    /// when it is finalized. But the spans finalized before are lost, and the
+    /// data recorded on this span before, like tags and logs, is lost too. It
+    /// returns an error then, though the span is kept. See
+    /// `TracingContext::should_report`.
+    pub fn set_sampling_priority(&mut self, keep: bool) -> Result<()> {
+        let priority = match self.priority.as_ref() {
+            Some(priority) => priority,
+            None => return Ok(()),
+        };
+        priority.store(keep, Ordering::Relaxed);
+        if !keep || !self.noop {
+            return Ok(());
+        }
+        self.noop = false;
+        self.kept = true;
+        if self.dropped {
+            return Err(Error::Context(
+                "data recorded before the sampling priority was set is lost.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Remember that data was discarded while the span may be kept later by
+    /// `set_sampling_priority`.
+    fn drop_data(&mut self) {
+        if self.priority.is_some() {
+            self.dropped = true;
+        }
+    }
+
+    /// Limit the number of references of the span. References beyond `max` are
+    /// dropped, and the span is marked by `refs.truncated` tag.
+    pub fn set_max_references(&mut self, max: usize) {
+        self.max_references = Some(max);
+    }
+
     fn add_segment_reference(&mut self, segment_reference: SegmentReference) {
+        if self.noop {
+            return;
+        }
+
+        if let Some(max) = self.max_references {
+            if self.span_internal.refs.len() >= max {
+                if self.get_tag(REFS_TRUNCATED_TAG).is_none() {
+                    self.add_tag((REFS_TRUNCATED_TAG, "true"));
+                }
+                return;
+            }
+        }
         self.span_internal.refs.push(segment_reference);
     }
 }
 
+// TODO(shikugawa): define this value in
+// https://github.com/apache/skywalking/blob/6452e0c2d983c85c392602d50436e8d8e421fec9/oap-server/server-starter/src/main/resources/component-libraries.yml
+const DEFAULT_COMPONENT_ID: i32 = 11000;
+
+/// Tag which holds the status code of the gRPC call.
+pub const RPC_STATUS_CODE_TAG: &str = "rpc.status_code";
+
+/// Tag which marks the span whose references exceeded the limit.
+pub const REFS_TRUNCATED_TAG: &str = "refs.truncated";
+
+/// Tags of the exit span which hold the host and the port of the peer.
+pub const NETWORK_PEER_ADDRESS_TAG: &str = "network.peer.address";
+pub const NETWORK_PEER_PORT_TAG: &str = "network.peer.port";
+
+/// Tags which tell OAP and operators the producer of the segment.
+pub const SDK_LANGUAGE_TAG: &str = "sdk.language";
+pub const SDK_VERSION_TAG: &str = "sdk.version";
+const SDK_LANGUAGE: &str = "Rust";
+
+/// Tag which holds the time of the span excluding its children in milliseconds.
+pub const SELF_TIME_TAG: &str = "self_time_ms";
+
+/// Tag which holds the location of the code which created the span.
+pub const SOURCE_LOCATION_TAG: &str = "source.location";
+
+/// Create an entry span of the context like `create_entry_span`, recording the
+/// location of the macro call as `source.location` tag.
+#[macro_export]
+macro_rules! entry_span {
+    ($context:expr, $operation_name:expr) => {
+        $context.create_entry_span($operation_name).map(|mut span| {
+            span.set_source_location(file!(), line!());
+            span
+        })
+    };
+}
+
+/// Run `$body` in an entry span of the context and return its value. The span is
+/// finalized when the body ends, including early return. The body can't borrow
+/// the context while the span is held, since the span guard borrows it
+/// mutably. If the span can't be created, e.g. the context is already reported,
+/// the body runs without it and the error is logged as a warning.
+#[macro_export]
+macro_rules! with_entry_span {
+    ($context:expr, $operation_name:expr, $body:block) => {{
+        let operation_name = $operation_name;
+        let _guard = match $context.entry_guard(operation_name) {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                $crate::__log::warn!("run {} without the entry span: {}", operation_name, e);
+                None
+            }
+        };
+        $body
+    }};
+}
+
+/// Run `$body` in an exit span of the context and return its value, like
+/// `with_entry_span!`. The span can't be created before the entry span.
+#[macro_export]
+macro_rules! with_exit_span {
+    ($context:expr, $operation_name:expr, $remote_peer:expr, $body:block) => {{
+        let operation_name = $operation_name;
+        let _guard = match $context.exit_guard(operation_name, $remote_peer) {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                $crate::__log::warn!("run {} without the exit span: {}", operation_name, e);
+                None
+            }
+        };
+        $body
+    }};
+}
+
+/// Tag which holds the statement executed by the database exit span.
+pub const DB_STATEMENT_TAG: &str = "db.statement";
+
+/// Tags of the cache exit span.
+pub const CACHE_TYPE_TAG: &str = "cache.type";
+pub const CACHE_OP_TAG: &str = "cache.op";
+pub const CACHE_KEY_TAG: &str = "cache.key";
+
+/// Cache keys can be arbitrarily large, so they are truncated in tags.
+const MAX_CACHE_KEY_LENGTH: usize = 128;
+
+/// Strip the query string and the fragment of the path.
+fn strip_query(path: &str) -> &str {
+    match path.find(|c| c == '?' || c == '#') {
+        Some(pos) => &path[..pos],
+        None => path,
+    }
+}
+
+/// Truncate `value` into at most `max` bytes on a char boundary.
+fn truncate_str(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Error returned when a non-entry span is created before the entry span.
+pub const ENTRY_SPAN_NOT_EXIST: &str = "entry span must be existed.";
+
+/// Error returned when a span is created after the context is converted into
+/// the segment object, since the span would never be reported.
+pub const CONTEXT_ALREADY_REPORTED: &str = "context has already been reported.";
+
+/// Put key-values into `pairs`, overwriting the value of the existing key.
+fn merge_key_values<I: IntoIterator<Item = (String, String)>>(
+    pairs: &mut Vec<KeyStringValuePair>,
+    key_values: I,
+) {
+    for (key, value) in key_values {
+        match pairs.iter_mut().find(|pair| pair.key == key) {
+            Some(pair) => pair.value = value,
+            None => pairs.push(KeyStringValuePair { key, value }),
+        }
+    }
+}
+
+/// Build a reference to the parent span described by `link`. Span IDs are only
+/// unique in a segment, so the reference always carries the parent segment ID
+/// along with the parent span ID.
+fn segment_reference(
+    trace_id: &str,
+    link: &PropagationContext,
+    ref_type: RefType,
+) -> SegmentReference {
+    // An empty instance breaks the instance level topology, so the parent
+    // service is used as the instance name instead.
+    let parent_service_instance = if link.parent_service_instance.trim().is_empty() {
+        log::warn!(
+            "parent service instance is empty, fall back to the parent service {}",
+            link.parent_service
+        );
+        link.parent_service.clone()
+    } else {
+        link.parent_service_instance.clone()
+    };
+
+    SegmentReference {
+        ref_type: ref_type as i32,
+        trace_id: trace_id.to_string(),
+        parent_trace_segment_id: link.parent_trace_segment_id.clone(),
+        parent_span_id: link.parent_span_id,
+        parent_service: link.parent_service.clone(),
+        parent_service_instance,
+        parent_endpoint: link.destination_endpoint.clone(),
+        network_address_used_at_peer: link.destination_address.clone(),
+    }
+}
+
+const MAX_TRACE_ID_LENGTH: usize = 128;
+
+/// Trace ID must be non-empty and consist of alphanumerics, `.`, `-` and `_`.
+fn check_trace_id(trace_id: &str) -> Result<()> {
+    if trace_id.is_empty() || trace_id.len() > MAX_TRACE_ID_LENGTH {
+        return Err(Error::Context("trace id must have 1 to 128 characters."));
+    }
+    if !trace_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+    {
+        return Err(Error::Context(
+            "trace id must consist of alphanumerics, '.', '-' and '_'.",
+        ));
+    }
+    Ok(())
+}
+
+/// Format the trace ID as OAP UI displays the IDs of the official agents, i.e.
+/// UUID in 32 lowercase hex digits without hyphens. Generated IDs are UUIDs in
+/// decimal, so they are converted. Other IDs, like the ones given through
+/// `with_trace_id` or propagated from other agents, are displayed as they are.
+pub fn display_trace_id(id: &str) -> String {
+    match id.parse::<u128>() {
+        Ok(uuid) if id.bytes().all(|b| b.is_ascii_digit()) => format!("{:032x}", uuid),
+        _ => id.to_string(),
+    }
+}
+
+/// Instance name made of the hostname and the process ID. The hostname falls back
+/// to `unknown` if it can't be resolved.
+fn host_instance_name() -> String {
+    let host = hostname::get()
+        .ok()
+        .and_then(|host| host.into_string().ok())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("{}@{}", host, std::process::id())
+}
+
+/// OAP groups segments by service and instance, so they must not be empty.
+/// It panics in debug build to catch misconfiguration early.
+fn check_names(service_name: &str, instance_name: &str) {
+    debug_assert!(
+        !service_name.trim().is_empty(),
+        "service name must not be empty."
+    );
+    debug_assert!(
+        !instance_name.trim().is_empty(),
+        "instance name must not be empty."
+    );
+}
+
 pub struct TracingContext {
     pub trace_id: String,
     pub trace_segment_id: String,
@@ -113,13 +655,78 @@ pub struct TracingContext {
     pub spans: Vec<Box<Span>>,
     time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
     segment_link: Option<PropagationContext>,
+    locals: HashMap<String, String>,
+    sampled: bool,
+    active_span_ids: Vec<i32>,
+    active_exit_span_ids: HashSet<i32>,
+    detached_spans: Vec<Box<Span>>,
+    span_meter: Option<SpanMeter>,
+    peer_from_url: bool,
+    namespace: Option<String>,
+    segment_link_type: RefType,
+    entry_endpoint: String,
+    health_check: bool,
+    sdk_tags: bool,
+    link_reference: bool,
+    time_offset_ms: i64,
+    peer_tags: bool,
+    max_references: Option<usize>,
+    force_report: Arc<AtomicBool>,
+    reported: AtomicBool,
+    size_limited: bool,
+    partially_reported: usize,
+    open_spans: Option<HashMap<i32, Box<Span>>>,
+}
+
+// `TracingContext` is moved across tasks in async handlers. This fails to
+// compile if any field makes it lose `Send` or `Sync`.
+fn _assert_send_sync()
+where
+    TracingContext: Send + Sync,
+    Span: Send + Sync,
+{
+}
+
+/// Violation of the invariants of the segment found by
+/// `TracingContext::try_convert_segment_object`. It holds the ID of the span.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SegmentValidationError {
+    /// More than one span have the same ID.
+    #[error("span id {0} is duplicated.")]
+    DuplicateSpanId(i32),
+    /// The span hasn't been finalized or closed.
+    #[error("span {0} is not closed.")]
+    SpanNotClosed(i32),
+    /// The span ends before it starts.
+    #[error("span {0} ends before it starts.")]
+    NegativeDuration(i32),
+}
+
+/// Snapshot of the context which can be sent to another thread, like workers of
+/// a thread pool. `TracingContext::restore` continues the trace from it with a
+/// cross thread reference.
+#[derive(Debug, Clone)]
+pub struct ContextSnapshot {
+    pub trace_id: String,
+    pub trace_segment_id: String,
+    pub span_id: i32,
+    pub service: String,
+    pub service_instance: String,
+    pub endpoint: String,
+    pub sampled: bool,
+}
+
+/// Meter which records the duration of every finalized span.
+struct SpanMeter {
+    name: String,
+    reporter: Arc<dyn MeterReporter + Sync + Send>,
 }
 
 impl TracingContext {
     /// Used to generate a new trace context. Typically called when no context has
     /// been propagated and a new trace is to be started.
     pub fn default(service_name: &str, instance_name: &str) -> Self {
-        let unix_time_fetcher = UnixTimeStampFetcher {};
+        let unix_time_fetcher = UnixTimeStampFetcher::default();
         TracingContext::default_internal(Arc::new(unix_time_fetcher), service_name, instance_name)
     }
 
@@ -128,6 +735,7 @@ impl TracingContext {
         service_name: &str,
         instance_name: &str,
     ) -> Self {
+        check_names(service_name, instance_name);
         TracingContext {
             trace_id: RandomGenerator::generate(),
             trace_segment_id: RandomGenerator::generate(),
@@ -137,9 +745,89 @@ impl TracingContext {
             time_fetcher,
             spans: Vec::new(),
             segment_link: None,
+            locals: HashMap::new(),
+            sampled: true,
+            active_span_ids: Vec::new(),
+            active_exit_span_ids: HashSet::new(),
+            detached_spans: Vec::new(),
+            span_meter: None,
+            peer_from_url: false,
+            namespace: None,
+            segment_link_type: RefType::CrossProcess,
+            entry_endpoint: String::default(),
+            health_check: false,
+            sdk_tags: false,
+            link_reference: true,
+            time_offset_ms: 0,
+            peer_tags: false,
+            max_references: None,
+            force_report: Arc::new(AtomicBool::new(false)),
+            reported: AtomicBool::new(false),
+            size_limited: false,
+            partially_reported: 0,
+            open_spans: None,
         }
     }
 
+    /// Generate a new trace context whose instance name is `<hostname>@<pid>`,
+    /// which is the common default of SkyWalking agents.
+    pub fn with_host_instance(
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+        service_name: &str,
+    ) -> Self {
+        TracingContext::default_internal(time_fetcher, service_name, &host_instance_name())
+    }
+
+    /// Generate a new trace context with the given trace ID instead of a random one.
+    /// It is useful to correlate the trace with an ID generated externally, like a
+    /// request ID issued by the edge proxy.
+    pub fn with_trace_id(
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+        service_name: &str,
+        instance_name: &str,
+        trace_id: String,
+    ) -> Result<Self> {
+        check_trace_id(&trace_id)?;
+        let mut context =
+            TracingContext::default_internal(time_fetcher, service_name, instance_name);
+        context.trace_id = trace_id;
+        Ok(context)
+    }
+
+    /// Reset this context to start a new trace, for reusing contexts from a pool.
+    /// The IDs are regenerated, and spans, locals and the link to the parent are
+    /// cleared. The time fetcher and the configurations, like the namespace and
+    /// the meter reporter, are kept. The allocated capacity of spans is kept too.
+    pub fn reset(&mut self) {
+        self.trace_id = RandomGenerator::generate();
+        self.trace_segment_id = RandomGenerator::generate();
+        self.next_span_id = 0;
+        self.spans.clear();
+        self.segment_link = None;
+        self.locals.clear();
+        self.sampled = true;
+        self.active_span_ids.clear();
+        self.active_exit_span_ids.clear();
+        self.detached_spans.clear();
+        self.segment_link_type = RefType::CrossProcess;
+        self.entry_endpoint.clear();
+        self.link_reference = true;
+        self.force_report = Arc::new(AtomicBool::new(false));
+        self.reported.store(false, Ordering::Relaxed);
+        self.size_limited = false;
+        self.partially_reported = 0;
+        if let Some(open_spans) = self.open_spans.as_mut() {
+            open_spans.clear();
+        }
+    }
+
+    /// Pre-allocate the store of spans for `capacity` spans. It avoids
+    /// reallocation for handlers which always create similar number of spans.
+    pub fn with_span_capacity(mut self, capacity: usize) -> Self {
+        self.spans.reserve(capacity);
+        self
+    }
+
     /// Generate a trace context using the propagated context.
     /// It is generally used when tracing is to be performed continuously.
     pub fn from_propagation_context(
@@ -147,7 +835,7 @@ impl TracingContext {
         instance_name: &str,
         context: PropagationContext,
     ) -> Self {
-        let unix_time_fetcher = UnixTimeStampFetcher {};
+        let unix_time_fetcher = UnixTimeStampFetcher::default();
         TracingContext::from_propagation_context_internal(
             Arc::new(unix_time_fetcher),
             service_name,
@@ -162,6 +850,7 @@ impl TracingContext {
         instance_name: &str,
         context: PropagationContext,
     ) -> Self {
+        check_names(service_name, instance_name);
         TracingContext {
             trace_id: context.parent_trace_id.clone(),
             trace_segment_id: RandomGenerator::generate(),
@@ -170,15 +859,196 @@ impl TracingContext {
             next_span_id: 0,
             time_fetcher,
             spans: Vec::new(),
+            sampled: context.do_sample,
             segment_link: Some(context),
+            locals: HashMap::new(),
+            active_span_ids: Vec::new(),
+            active_exit_span_ids: HashSet::new(),
+            detached_spans: Vec::new(),
+            span_meter: None,
+            peer_from_url: false,
+            namespace: None,
+            segment_link_type: RefType::CrossProcess,
+            entry_endpoint: String::default(),
+            health_check: false,
+            sdk_tags: false,
+            link_reference: true,
+            time_offset_ms: 0,
+            peer_tags: false,
+            max_references: None,
+            force_report: Arc::new(AtomicBool::new(false)),
+            reported: AtomicBool::new(false),
+            size_limited: false,
+            partially_reported: 0,
+            open_spans: None,
         }
     }
 
+    /// Generate a trace context using the propagated context borrowed from the
+    /// caller, who can keep using it afterwards.
+    pub fn from_propagation_context_ref(
+        service_name: &str,
+        instance_name: &str,
+        context: &PropagationContext,
+    ) -> Self {
+        let unix_time_fetcher = UnixTimeStampFetcher::default();
+        TracingContext::from_propagation_context_ref_internal(
+            Arc::new(unix_time_fetcher),
+            service_name,
+            instance_name,
+            context,
+        )
+    }
+
+    pub fn from_propagation_context_ref_internal(
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+        service_name: &str,
+        instance_name: &str,
+        context: &PropagationContext,
+    ) -> Self {
+        TracingContext::from_propagation_context_internal(
+            time_fetcher,
+            service_name,
+            instance_name,
+            context.clone(),
+        )
+    }
+
+    /// Generate a trace context using the propagated context, but the entry span
+    /// doesn't refer to the parent. It is for relays which decode the incoming
+    /// context only to inspect it.
+    pub fn from_propagation_context_without_ref(
+        service_name: &str,
+        instance_name: &str,
+        context: PropagationContext,
+    ) -> Self {
+        let unix_time_fetcher = UnixTimeStampFetcher::default();
+        TracingContext::from_propagation_context_without_ref_internal(
+            Arc::new(unix_time_fetcher),
+            service_name,
+            instance_name,
+            context,
+        )
+    }
+
+    pub fn from_propagation_context_without_ref_internal(
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+        service_name: &str,
+        instance_name: &str,
+        context: PropagationContext,
+    ) -> Self {
+        let mut context = TracingContext::from_propagation_context_internal(
+            time_fetcher,
+            service_name,
+            instance_name,
+            context,
+        );
+        context.link_reference = false;
+        context
+    }
+
+    /// Take the snapshot to continue this trace on another thread. The active
+    /// span becomes the parent.
+    pub fn snapshot(&self) -> ContextSnapshot {
+        ContextSnapshot {
+            trace_id: self.trace_id.clone(),
+            trace_segment_id: self.trace_segment_id.clone(),
+            span_id: self
+                .active_span_id()
+                .unwrap_or_else(|| (self.next_span_id - 1).max(0)),
+            service: self.service.clone(),
+            service_instance: self.service_instance.clone(),
+            endpoint: self.entry_endpoint.clone(),
+            sampled: self.sampled,
+        }
+    }
+
+    /// Continue the trace of `snapshot` on this thread, as a new segment of the
+    /// same service. The entry span refers to the origin span as cross thread.
+    pub fn restore(
+        snapshot: ContextSnapshot,
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+    ) -> Self {
+        let service = snapshot.service.clone();
+        let instance = snapshot.service_instance.clone();
+        let link = PropagationContext::new(
+            snapshot.sampled,
+            snapshot.trace_id,
+            snapshot.trace_segment_id,
+            snapshot.span_id,
+            snapshot.service,
+            snapshot.service_instance,
+            snapshot.endpoint,
+            String::default(),
+        );
+        let mut context = TracingContext::from_propagation_context_internal(
+            time_fetcher,
+            &service,
+            &instance,
+            link,
+        );
+        context.segment_link_type = RefType::CrossThread;
+        context
+    }
+
+    /// Decode `sw8` header value, continue the trace and create the entry span
+    /// at once. It is the most common pattern of servers.
+    pub fn entry_from_sw8(
+        time_fetcher: Arc<dyn TimeFetcher + Sync + Send>,
+        service_name: &str,
+        instance_name: &str,
+        sw8: &str,
+        operation_name: &str,
+    ) -> Result<(TracingContext, Box<Span>)> {
+        let propagation = decode_propagation(sw8)?;
+        let mut context = TracingContext::from_propagation_context_internal(
+            time_fetcher,
+            service_name,
+            instance_name,
+            propagation,
+        );
+        let span = context.create_entry_span(operation_name)?;
+        Ok((context, span))
+    }
+
+    /// Link this context to the parent whose IDs are known from a non-standard
+    /// source, without `sw8`. The trace is continued and the cross process
+    /// reference to the parent is added onto the entry span, so it must be
+    /// called before the entry span is created.
+    #[allow(clippy::too_many_arguments)]
+    pub fn link_parent(
+        &mut self,
+        trace_id: &str,
+        segment_id: &str,
+        span_id: i32,
+        service: &str,
+        instance: &str,
+        endpoint: &str,
+        address: &str,
+    ) -> Result<()> {
+        if self.next_span_id >= 1 {
+            return Err(Error::Context("entry span have already exist."));
+        }
+
+        self.trace_id = trace_id.to_string();
+        self.segment_link = Some(PropagationContext::new(
+            self.sampled,
+            trace_id.to_string(),
+            segment_id.to_string(),
+            span_id,
+            service.to_string(),
+            instance.to_string(),
+            endpoint.to_string(),
+            address.to_string(),
+        ));
+        Ok(())
+    }
+
     pub fn entry<F: FnMut(&Span)>(
         &mut self,
         operation_name: &str,
         mut process_fn: F,
-    ) -> Result<(), &str> {
+    ) -> Result<()> {
         match self.create_entry_span(operation_name) {
             Ok(mut span) => {
                 process_fn(span.as_ref());
@@ -191,56 +1061,59 @@ impl TracingContext {
 
     /// Create a new entry span, which is an initiator of collection of spans.
     /// This should be called by invocation of the function which is triggered by
-    /// external service.
-    pub fn create_entry_span(&mut self, operation_name: &str) -> Result<Box<Span>, &'static str> {
+    /// external service. The query string and the fragment of `operation_name`
+    /// are stripped like `Span::set_endpoint_name`.
+    pub fn create_entry_span(&mut self, operation_name: &str) -> Result<Box<Span>> {
+        self.create_entry_span_internal(operation_name, false)
+    }
+
+    fn create_entry_span_internal(
+        &mut self,
+        operation_name: &str,
+        health_check: bool,
+    ) -> Result<Box<Span>> {
+        self.check_not_reported()?;
         if self.next_span_id >= 1 {
-            return Err("entry span have already exist.");
+            return Err(Error::Context("entry span have already exist."));
         }
 
-        let mut span = Box::new(Span::new(
-            self.next_span_id,
-            operation_name.to_string(),
-            String::default(),
-            SpanType::Entry,
-            SpanLayer::Http,
-            false,
-            self.time_fetcher.clone(),
-        ));
+        let operation_name = strip_query(operation_name);
+        let mut span = self.new_span(operation_name, "", SpanType::Entry, SpanLayer::Http);
+        self.entry_endpoint = operation_name.to_string();
+        self.health_check = health_check;
+        self.decorate_entry_span(&mut span);
+        Ok(span)
+    }
 
-        if self.segment_link.is_some() {
-            span.add_segment_reference(SegmentReference {
-                ref_type: RefType::CrossProcess as i32,
-                trace_id: self.trace_id.clone(),
-                parent_trace_segment_id: self
-                    .segment_link
-                    .as_ref()
-                    .unwrap()
-                    .parent_trace_segment_id
-                    .clone(),
-                parent_span_id: self.segment_link.as_ref().unwrap().parent_span_id,
-                parent_service: self.segment_link.as_ref().unwrap().parent_service.clone(),
-                parent_service_instance: self
-                    .segment_link
-                    .as_ref()
-                    .unwrap()
-                    .parent_service_instance
-                    .clone(),
-                parent_endpoint: self
-                    .segment_link
-                    .as_ref()
-                    .unwrap()
-                    .destination_endpoint
-                    .clone(),
-                network_address_used_at_peer: self
-                    .segment_link
-                    .as_ref()
-                    .unwrap()
-                    .destination_address
-                    .clone(),
-            });
+    /// Record the data of the entry span which the context knows, like the
+    /// reference to the parent. It is also used to restore the entry span which
+    /// was created as a no-op span and kept by `Span::set_sampling_priority`.
+    fn decorate_entry_span(&self, span: &mut Span) {
+        if let Some(max) = self.max_references {
+            span.set_max_references(max);
+        }
+        if self.is_skip_analysis() || self.health_check {
+            span.span_internal.skip_analysis = true;
+        }
+        if self.sdk_tags && !self.health_check {
+            span.add_tag((SDK_LANGUAGE_TAG, SDK_LANGUAGE));
+            span.add_tag((SDK_VERSION_TAG, env!("CARGO_PKG_VERSION")));
+        }
+
+        if let Some(link) = self.segment_link.as_ref().filter(|_| self.link_reference) {
+            span.add_segment_reference(segment_reference(
+                &self.trace_id,
+                link,
+                self.segment_link_type,
+            ));
         }
-        self.next_span_id += 1;
-        Ok(span)
+    }
+
+    /// Create an entry span for health check endpoints. Health checks are high
+    /// volume and low value, so the span skips the analysis of OAP. Nothing else
+    /// than the entry span itself is recorded, even the tags of `set_sdk_tags`.
+    pub fn health_check_entry(&mut self, operation_name: &str) -> Result<Box<Span>> {
+        self.create_entry_span_internal(operation_name, true)
     }
 
     pub fn exit<F: FnMut(&Span)>(
@@ -248,7 +1121,7 @@ impl TracingContext {
         operation_name: &str,
         remote_peer: &str,
         mut process_fn: F,
-    ) -> Result<(), &str> {
+    ) -> Result<()> {
         match self.create_exit_span(operation_name, remote_peer) {
             Ok(mut span) => {
                 process_fn(span.as_ref());
@@ -266,49 +1139,726 @@ impl TracingContext {
         &mut self,
         operation_name: &str,
         remote_peer: &str,
-    ) -> Result<Box<Span>, &'static str> {
-        if self.next_span_id == 0 {
-            return Err("entry span must be existed.");
+    ) -> Result<Box<Span>> {
+        self.create_exit_span_with_component(operation_name, remote_peer, SpanLayer::Http, None)
+    }
+
+    /// Create a new exit span with the given layer. If `component_id` is set, it
+    /// overrides the default component, e.g. to tell gRPC calls (23) from HTTP
+    /// calls (2) made by the same client.
+    pub fn create_exit_span_with_component(
+        &mut self,
+        operation_name: &str,
+        remote_peer: &str,
+        span_layer: SpanLayer,
+        component_id: Option<i32>,
+    ) -> Result<Box<Span>> {
+        self.check_entry_span_exists()?;
+
+        let mut span = self.new_span(operation_name, "", SpanType::Exit, span_layer);
+        if span.noop {
+            return Ok(span);
         }
+        span.span_internal.peer = if self.peer_from_url {
+            authority_from_url(remote_peer).unwrap_or_else(|| remote_peer.to_string())
+        } else {
+            remote_peer.to_string()
+        };
+        let peer_tags = if self.peer_tags {
+            let (host, port) = split_peer(&span.span_internal.peer);
+            Some((host.to_string(), port))
+        } else {
+            None
+        };
 
-        let span = Box::new(Span::new(
-            self.next_span_id,
-            operation_name.to_string(),
-            remote_peer.to_string(),
-            SpanType::Exit,
-            SpanLayer::Http,
-            false,
-            self.time_fetcher.clone(),
-        ));
-        self.next_span_id += 1;
+        if let Some(component_id) = component_id {
+            span.set_component_id(component_id);
+        }
+        if let Some((host, port)) = peer_tags {
+            span.add_tag((NETWORK_PEER_ADDRESS_TAG, &host));
+            if let Some(port) = port {
+                span.add_tag((NETWORK_PEER_PORT_TAG, &port.to_string()));
+            }
+        }
+        Ok(span)
+    }
+
+    /// Create a new exit span which calls the database at `remote_peer`. The
+    /// executed statement is recorded as `db.statement` tag. The peer is handled
+    /// in the same way as `create_exit_span`.
+    pub fn create_db_exit_span(
+        &mut self,
+        operation_name: &str,
+        remote_peer: &str,
+        statement: &str,
+    ) -> Result<Box<Span>> {
+        let mut span = self.create_exit_span_with_component(
+            operation_name,
+            remote_peer,
+            SpanLayer::Database,
+            None,
+        )?;
+        span.add_tag((DB_STATEMENT_TAG, statement));
         Ok(span)
     }
 
+    /// Create a new exit span which calls the cache, like Redis or Memcached, at
+    /// `remote_peer`. The component is resolved from `cache_type`. The key is
+    /// truncated to 128 bytes. The peer is handled in the same way as
+    /// `create_exit_span`.
+    pub fn create_cache_exit_span(
+        &mut self,
+        remote_peer: &str,
+        cache_type: &str,
+        command: &str,
+        key: &str,
+    ) -> Result<Box<Span>> {
+        let operation_name = if self.should_report() {
+            format!("{}/{}", cache_type, command)
+        } else {
+            String::new()
+        };
+        let mut span = self.create_exit_span_with_component(
+            &operation_name,
+            remote_peer,
+            SpanLayer::Cache,
+            component_id(cache_type),
+        )?;
+        span.add_tag((CACHE_TYPE_TAG, cache_type));
+        span.add_tag((CACHE_OP_TAG, command));
+        span.add_tag((CACHE_KEY_TAG, truncate_str(key, MAX_CACHE_KEY_LENGTH)));
+        Ok(span)
+    }
+
+    /// Create a new local span, which expresses an in-process operation like
+    /// a function call. It doesn't have any remote peer.
+    pub fn create_local_span(&mut self, operation_name: &str) -> Result<Box<Span>> {
+        self.check_entry_span_exists()?;
+
+        Ok(self.new_span(operation_name, "", SpanType::Local, SpanLayer::Unknown))
+    }
+
+    /// Record a span of an operation which has already finished, measured
+    /// externally as `duration`. The span ends now and is finalized immediately.
+    pub fn record_span_with_duration(
+        &mut self,
+        operation_name: &str,
+        duration: Duration,
+        span_type: SpanType,
+    ) -> Result<()> {
+        let mut span = match span_type {
+            SpanType::Entry => self.create_entry_span(operation_name)?,
+            SpanType::Exit => self.create_exit_span(operation_name, "")?,
+            SpanType::Local => self.create_local_span(operation_name)?,
+        };
+
+        let end_time = self.time_fetcher.get() + self.time_offset_ms;
+        span.span_internal.start_time = end_time - duration.as_millis() as i64;
+        span.start_nanos = span.span_internal.start_time as i128 * NANOS_PER_MILLI;
+        span.set_end_time(end_time);
+        self.store_span(span);
+        Ok(())
+    }
+
+    /// Allocate the next span. If this context is not reported, a no-op span
+    /// which only keeps its identity is returned instead, so that nothing is
+    /// allocated for the span data. It is recorded once it is kept by
+    /// `Span::set_sampling_priority`.
+    fn new_span(
+        &mut self,
+        operation_name: &str,
+        remote_peer: &str,
+        span_type: SpanType,
+        span_layer: SpanLayer,
+    ) -> Box<Span> {
+        // Span IDs start from 0 in every segment, and siblings never share
+        // the ID. The innermost active span is the parent, except for exit
+        // spans. They are leaves, so concurrent exit spans are siblings. The
+        // first span has -1 as the parent, since it doesn't have one.
+        let parent_span_id = self
+            .active_span_ids
+            .iter()
+            .rev()
+            .find(|id| !self.active_exit_span_ids.contains(id))
+            .copied()
+            .unwrap_or(-1);
+        let span = if self.should_report() {
+            Span::new(
+                self.next_span_id,
+                parent_span_id,
+                operation_name.to_string(),
+                remote_peer.to_string(),
+                span_type,
+                span_layer,
+                false,
+                self.span_time_fetcher(),
+            )
+        } else {
+            let mut span = Span::noop(self.span_time_fetcher());
+            span.span_internal.span_id = self.next_span_id;
+            span.span_internal.parent_span_id = parent_span_id;
+            span.span_internal.span_type = span_type as i32;
+            span.span_internal.span_layer = span_layer as i32;
+            span.span_internal.component_id = DEFAULT_COMPONENT_ID;
+            span.priority = Some(self.force_report.clone());
+            span
+        };
+        self.active_span_ids.push(span.span_internal.span_id);
+        if span_type == SpanType::Exit {
+            self.active_exit_span_ids.insert(span.span_internal.span_id);
+        }
+        self.next_span_id += 1;
+        let span = Box::new(span);
+        if let Some(open_spans) = self.open_spans.as_mut() {
+            open_spans.insert(span.span_internal.span_id, span.open_copy());
+        }
+        span
+    }
+
+    /// Give the entry span which was kept by `Span::set_sampling_priority` the
+    /// data which the no-op span didn't record. Other spans only keep their
+    /// identity and the data recorded after they were kept.
+    fn restore_kept_span(&self, span: &mut Span) {
+        if span.span_internal.span_type != SpanType::Entry as i32 {
+            return;
+        }
+        if span.span_internal.operation_name.is_empty() {
+            span.span_internal.operation_name = self.entry_endpoint.clone();
+        }
+        self.decorate_entry_span(span);
+    }
+
+    /// Time fetcher of spans, which applies the time offset.
+    fn span_time_fetcher(&self) -> Arc<dyn TimeFetcher + Sync + Send> {
+        if self.time_offset_ms == 0 {
+            return self.time_fetcher.clone();
+        }
+        Arc::new(OffsetTimeFetcher::new(
+            self.time_fetcher.clone(),
+            self.time_offset_ms,
+        ))
+    }
+
+    /// Keep a finished span. No-op spans of the context which is not sampled
+    /// are dropped here, so they will not be reported.
+    fn store_span(&mut self, mut span: Box<Span>) {
+        let span_id = span.span_internal.span_id;
+        if let Some(pos) = self.active_span_ids.iter().rposition(|id| *id == span_id) {
+            self.active_span_ids.remove(pos);
+        }
+        self.active_exit_span_ids.remove(&span_id);
+        if let Some(open_spans) = self.open_spans.as_mut() {
+            open_spans.remove(&span_id);
+        }
+        if span.noop {
+            return;
+        }
+        if span.kept {
+            self.restore_kept_span(&mut span);
+        }
+        if let Some(meter) = self.span_meter.as_ref() {
+            let duration = span.span_internal.end_time - span.span_internal.start_time;
+            let labels = [(
+                "operation".to_string(),
+                span.span_internal.operation_name.clone(),
+            )];
+            meter.reporter.record(&meter.name, duration as f64, &labels);
+        }
+        self.spans.push(span);
+    }
+
+    /// Spans can't be created once the context is converted into the segment
+    /// object by `convert_segment_object`, until it is `reset`.
+    fn check_not_reported(&self) -> Result<()> {
+        if self.reported.load(Ordering::Relaxed) {
+            return Err(Error::Context(CONTEXT_ALREADY_REPORTED));
+        }
+        Ok(())
+    }
+
+    /// All spans except for the entry span must be created after the entry span.
+    /// Every non-entry span creator should check it with this.
+    fn check_entry_span_exists(&self) -> Result<()> {
+        self.check_not_reported()?;
+        if self.next_span_id == 0 {
+            return Err(Error::Context(ENTRY_SPAN_NOT_EXIST));
+        }
+        Ok(())
+    }
+
     pub fn finalize_span(&mut self, mut span: Box<Span>) {
         span.close();
-        self.spans.push(span);
+        self.store_span(span);
+    }
+
+    /// Create a new entry span which is finalized when the returned guard is dropped.
+    pub fn entry_guard(&mut self, operation_name: &str) -> Result<SpanGuard<'_>> {
+        let span = self.create_entry_span(operation_name)?;
+        Ok(SpanGuard::new(self, span))
+    }
+
+    /// Create a new exit span which is finalized when the returned guard is dropped.
+    pub fn exit_guard(&mut self, operation_name: &str, remote_peer: &str) -> Result<SpanGuard<'_>> {
+        let span = self.create_exit_span(operation_name, remote_peer)?;
+        Ok(SpanGuard::new(self, span))
+    }
+
+    /// Create a new local span which is finalized when the returned guard is dropped.
+    pub fn local_guard(&mut self, operation_name: &str) -> Result<SpanGuard<'_>> {
+        let span = self.create_local_span(operation_name)?;
+        Ok(SpanGuard::new(self, span))
+    }
+
+    /// Finalize the span which ended at `end_time`, instead of now. It is useful
+    /// when the operation is known to have finished earlier.
+    pub fn finalize_span_at(&mut self, mut span: Box<Span>, end_time: i64) {
+        span.set_end_time(end_time);
+        self.store_span(span);
+    }
+
+    /// Hand over the span whose operation might never complete, like a callback
+    /// which may be leaked. It is finalized by `sweep_expired_spans` with a
+    /// `timeout` tag once its deadline passed, unless it is taken back with
+    /// `take_detached_span` before that. Returns the span ID.
+    pub fn detach_span(&mut self, span: Box<Span>) -> i32 {
+        let span_id = span.span_internal.span_id;
+        if span.noop {
+            // Nothing is reported, so it is dropped right away.
+            self.store_span(span);
+        } else {
+            self.detached_spans.push(span);
+        }
+        span_id
+    }
+
+    /// Take back the detached span to finalize it as usual.
+    pub fn take_detached_span(&mut self, span_id: i32) -> Option<Box<Span>> {
+        let pos = self
+            .detached_spans
+            .iter()
+            .position(|span| span.span_internal.span_id == span_id)?;
+        Some(self.detached_spans.remove(pos))
+    }
+
+    /// Finalize detached spans which passed their deadline as errored spans
+    /// tagged with `timeout`. Returns the number of finalized spans.
+    pub fn sweep_expired_spans(&mut self) -> usize {
+        let (expired, alive): (Vec<_>, Vec<_>) = self
+            .detached_spans
+            .drain(..)
+            .partition(|span| span.is_expired());
+        self.detached_spans = alive;
+
+        let count = expired.len();
+        for mut span in expired {
+            span.add_tag(("timeout", "true"));
+            span.set_error(true);
+            self.finalize_span(span);
+        }
+        count
+    }
+
+    /// Keep a copy of every span when it is created, so that
+    /// `finalize_active_spans` can finalize spans which are never finalized by
+    /// their owners. Data recorded on the span after its creation is not in the
+    /// copy.
+    pub fn set_track_open_spans(&mut self, enabled: bool) {
+        self.open_spans = if enabled { Some(HashMap::new()) } else { None };
+    }
+
+    /// Finalize all the spans which are still active, from the innermost one.
+    /// Detached spans are finalized as they are, and the others from their
+    /// copies kept by `set_track_open_spans`. Spans without either are given up.
+    pub fn finalize_active_spans(&mut self) {
+        while let Some(span_id) = self.active_span_ids.last().copied() {
+            let span = match self.take_detached_span(span_id) {
+                Some(span) => Some(span),
+                None => self
+                    .open_spans
+                    .as_mut()
+                    .and_then(|open_spans| open_spans.remove(&span_id)),
+            };
+            match span {
+                Some(mut span) => {
+                    // The copy was taken when the span was created, so it is
+                    // kept if the context was forced to be reported since then.
+                    let report = self.should_report();
+                    span.kept |= span.noop && report;
+                    span.noop = !report;
+                    self.finalize_span(span);
+                }
+                None => {
+                    self.active_span_ids.pop();
+                    self.active_exit_span_ids.remove(&span_id);
+                }
+            }
+        }
     }
 
     pub fn finalize_span_for_test(&self, span: &mut Box<Span>) {
         span.close();
     }
 
+    /// Whether this context started a new trace. It is false if the context
+    /// continues the propagated one.
+    pub fn is_root(&self) -> bool {
+        self.segment_link.is_none()
+    }
+
+    /// The entry endpoint of the parent, if this context is continued.
+    pub fn parent_endpoint(&self) -> Option<&str> {
+        self.segment_link
+            .as_ref()
+            .map(|link| link.destination_endpoint.as_str())
+    }
+
+    /// The address which the parent requested to, if this context is continued.
+    pub fn parent_address(&self) -> Option<&str> {
+        self.segment_link
+            .as_ref()
+            .map(|link| link.destination_address.as_str())
+    }
+
+    /// Whether spans of this context are sampled. If not, span creators return
+    /// no-op spans and nothing is recorded.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Whether the parent asked to skip the analysis by `sw8-x`. The entry span
+    /// skips the analysis then, and the flag is propagated to downstream.
+    pub fn is_skip_analysis(&self) -> bool {
+        self.segment_link
+            .as_ref()
+            .map_or(false, |link| link.skip_analysis)
+    }
+
+    /// Whether the parent reached the limit of spans, propagated by `sw8-x`.
+    pub fn is_span_limit_reached(&self) -> bool {
+        self.segment_link
+            .as_ref()
+            .map_or(false, |link| link.span_limit_reached)
+    }
+
+    /// Whether this context should be reported. It is true if the context is
+    /// sampled, or a span forced it by `Span::set_sampling_priority`.
+    pub fn should_report(&self) -> bool {
+        self.sampled || self.force_report.load(Ordering::Relaxed)
+    }
+
+    /// Decide whether this context is sampled, e.g. not to sample a root trace.
+    /// The decision is propagated to downstream by `encode_propagation`. It
+    /// should be set before creating spans, since it only affects new spans.
+    pub fn set_sampled(&mut self, sampled: bool) {
+        self.sampled = sampled;
+    }
+
+    /// The number of finalized spans which will be in the segment.
+    pub fn span_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// The number of spans which have been created but not finalized yet.
+    pub fn active_span_count(&self) -> usize {
+        self.active_span_ids.len()
+    }
+
+    /// ID of the span which was created most recently and hasn't been finalized yet.
+    pub fn active_span_id(&self) -> Option<i32> {
+        self.active_span_ids.last().copied()
+    }
+
+    /// Make the context to be propagated to downstream without creating an exit
+    /// span, for transports which are not modeled as calls. The active span
+    /// becomes the parent. If there is no active span, the most recently created
+    /// span is the parent like `encode_propagation`. The parent endpoint is the
+    /// entry endpoint, and `endpoint` is used only if there is no entry span.
+    pub fn make_downstream_context(&self, endpoint: &str, address: &str) -> PropagationContext {
+        PropagationContext::new(
+            self.sampled,
+            self.trace_id.clone(),
+            self.trace_segment_id.clone(),
+            self.active_span_id()
+                .unwrap_or_else(|| (self.next_span_id - 1).max(0)),
+            self.namespaced_service(),
+            self.service_instance.clone(),
+            self.entry_endpoint().unwrap_or(endpoint).to_string(),
+            address.to_string(),
+        )
+    }
+
+    /// Operation name of the entry span of this segment, if it has been created.
+    pub fn entry_endpoint(&self) -> Option<&str> {
+        if self.entry_endpoint.is_empty() {
+            None
+        } else {
+            Some(&self.entry_endpoint)
+        }
+    }
+
+    /// Record the duration of every finalized span in milliseconds into the meter
+    /// `name` of `reporter`, labeled with its operation name. It saves recording
+    /// a latency histogram along with every span by hand.
+    pub fn set_meter_reporter(
+        &mut self,
+        name: &str,
+        reporter: Arc<dyn MeterReporter + Sync + Send>,
+    ) {
+        self.span_meter = Some(SpanMeter {
+            name: name.to_string(),
+            reporter,
+        });
+    }
+
+    /// If enabled, `create_exit_span` accepts the full URL as `remote_peer` and
+    /// uses its authority, like `consumer:8082` of `http://consumer:8082/pong`,
+    /// as the peer. Peers which are not URLs are used as they are.
+    pub fn set_peer_from_url(&mut self, enabled: bool) {
+        self.peer_from_url = enabled;
+    }
+
+    /// Shift the start and end time of spans created after this call by
+    /// `offset_ms`, when the clock of this host is known to be off against the
+    /// collector. Durations are not affected.
+    pub fn set_time_offset_ms(&mut self, offset_ms: i64) {
+        self.time_offset_ms = offset_ms;
+    }
+
+    /// Tag the entry span with `sdk.language=Rust` and `sdk.version`, as a hint
+    /// of the producer for OAP versions which interpret some fields differently.
+    pub fn set_sdk_tags(&mut self, enabled: bool) {
+        self.sdk_tags = enabled;
+    }
+
+    /// Separate traces from the other clusters which share the collector. The
+    /// service name is reported as `namespace::service`, following SkyWalking.
+    pub fn set_namespace(&mut self, namespace: &str) {
+        self.namespace = Some(namespace.to_string());
+    }
+
+    /// The service name which is reported and propagated, including the namespace.
+    pub fn namespaced_service(&self) -> String {
+        match self.namespace.as_ref() {
+            Some(namespace) => format!("{}::{}", namespace, self.service),
+            None => self.service.clone(),
+        }
+    }
+
+    /// If enabled, `create_exit_span` tags the span with `network.peer.address`
+    /// and `network.peer.port` derived from the peer. The port tag is omitted
+    /// if the peer doesn't have a port.
+    pub fn set_peer_tags(&mut self, enabled: bool) {
+        self.peer_tags = enabled;
+    }
+
+    /// Mark the segment as size limited, when the application dropped some data
+    /// of it, like logs, by its own limits. It is reported as `is_size_limited`.
+    pub fn set_size_limited(&mut self, size_limited: bool) {
+        self.size_limited = size_limited;
+    }
+
+    /// Limit the number of references of the entry span, e.g. for batch
+    /// consumers linking many producers. See `Span::set_max_references`.
+    pub fn set_max_references(&mut self, max: usize) {
+        self.max_references = Some(max);
+    }
+
+    /// Set metadata which is only visible in this context. Unlike propagated
+    /// values, it is never encoded into headers.
+    pub fn set_local(&mut self, key: &str, value: &str) {
+        self.locals.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get_local(&self, key: &str) -> Option<&str> {
+        self.locals.get(key).map(|value| value.as_str())
+    }
+
+    /// Whether any finalized span is errored. It can be used to decide whether
+    /// the trace should be sampled forcibly.
+    pub fn has_error(&self) -> bool {
+        self.spans.iter().any(|span| span.span_internal.is_error)
+    }
+
     /// It converts tracing context into segment object.
-    /// This conversion should be done before sending segments into OAP.
+    /// This conversion should be done before sending segments into OAP. Spans
+    /// can't be created after that, since they would never be reported.
     pub fn convert_segment_object(&self) -> SegmentObject {
-        let mut objects = Vec::<SpanObject>::new();
+        self.convert_segment_object_with_service(&self.namespaced_service(), &self.service_instance)
+    }
 
+    /// Tag every finalized span with its self time, i.e. its duration excluding
+    /// the durations of its finalized children. It should be called after all
+    /// spans are finalized, before the context is converted into the segment.
+    pub fn add_self_time_tags(&mut self) {
+        let mut children_durations = HashMap::<i32, i64>::new();
         for span in self.spans.iter() {
+            let span = &span.span_internal;
+            *children_durations.entry(span.parent_span_id).or_insert(0) +=
+                span.end_time - span.start_time;
+        }
+
+        for span in self.spans.iter_mut() {
+            let duration = span.span_internal.end_time - span.span_internal.start_time;
+            let children_duration = children_durations
+                .get(&span.span_internal.span_id)
+                .copied()
+                .unwrap_or(0);
+            // Async children may run longer than their parent.
+            let self_time = (duration - children_duration).max(0);
+            span.add_tags(vec![(SELF_TIME_TAG.to_string(), self_time.to_string())]);
+        }
+    }
+
+    /// Report the spans which have been finalized so far, while the others,
+    /// like the entry span of a long stream, are still open. It makes the trace
+    /// visible before the whole operation ends. Every call reports only the spans
+    /// finalized since the previous call. All the pieces, including the final
+    /// segment which holds the rest of the spans, share the segment ID, so that
+    /// OAP links the spans to their parents across the pieces.
+    pub fn report_partial<R: Reporter>(&mut self, reporter: &mut R) -> Result<()> {
+        let segment = self.build_segment_object(&self.namespaced_service(), &self.service_instance);
+        self.partially_reported = self.spans.len();
+        reporter.report(segment)
+    }
+
+    /// Same as `convert_segment_object`, but it checks the invariants of the
+    /// segment and returns all the violations instead, if any.
+    pub fn try_convert_segment_object(&self) -> Result<SegmentObject, Vec<SegmentValidationError>> {
+        let segment = self.build_segment_object(&self.namespaced_service(), &self.service_instance);
+        let mut errors = Vec::new();
+
+        for span_id in self.active_span_ids.iter() {
+            errors.push(SegmentValidationError::SpanNotClosed(*span_id));
+        }
+
+        let mut span_ids = HashSet::new();
+        for span in segment.spans.iter() {
+            if !span_ids.insert(span.span_id) {
+                errors.push(SegmentValidationError::DuplicateSpanId(span.span_id));
+            }
+            if span.end_time == 0 {
+                errors.push(SegmentValidationError::SpanNotClosed(span.span_id));
+            } else if span.end_time < span.start_time {
+                errors.push(SegmentValidationError::NegativeDuration(span.span_id));
+            }
+        }
+
+        if errors.is_empty() {
+            self.reported.store(true, Ordering::Relaxed);
+            Ok(segment)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Convert the finished context into segment object, moving the spans out
+    /// instead of cloning them like `convert_segment_object`. The segment can be
+    /// sent to the reporter on another thread as is.
+    pub fn into_segment(self) -> SegmentObject {
+        let service = self.namespaced_service();
+        let mut objects: Vec<SpanObject> = self
+            .spans
+            .into_iter()
+            .skip(self.partially_reported)
+            .map(|span| span.span_internal)
+            .collect();
+        objects.sort_by_key(|span| span.span_id);
+
+        SegmentObject {
+            trace_id: self.trace_id,
+            trace_segment_id: self.trace_segment_id,
+            spans: objects,
+            service,
+            service_instance: self.service_instance,
+            is_size_limited: self.size_limited,
+            ..Default::default()
+        }
+    }
+
+    /// It converts tracing context into segment object which belongs to the given
+    /// service and instance instead of the context's ones. It is useful for relays
+    /// which forward segments on behalf of many services.
+    pub fn convert_segment_object_with_service(
+        &self,
+        service_name: &str,
+        instance_name: &str,
+    ) -> SegmentObject {
+        self.reported.store(true, Ordering::Relaxed);
+        self.build_segment_object(service_name, instance_name)
+    }
+
+    fn build_segment_object(&self, service_name: &str, instance_name: &str) -> SegmentObject {
+        let mut objects = Vec::<SpanObject>::new();
+
+        for span in self.spans.iter().skip(self.partially_reported) {
             objects.push(span.span_internal.clone());
         }
+        // Spans are finalized in arbitrary order in async code. IDs are fixed
+        // when spans are created, so sort them to keep segments deterministic.
+        objects.sort_by_key(|span| span.span_id);
 
         SegmentObject {
             trace_id: self.trace_id.to_string(),
             trace_segment_id: self.trace_segment_id.to_string(),
             spans: objects,
-            service: self.service.clone(),
-            service_instance: self.service_instance.clone(),
-            is_size_limited: false,
+            service: service_name.to_string(),
+            service_instance: instance_name.to_string(),
+            is_size_limited: self.size_limited,
+            ..Default::default()
+        }
+    }
+}
+
+/// Spawn a task which sweeps expired detached spans of `context` every `period`.
+/// The task runs until it is aborted through the returned handle.
+pub fn spawn_span_sweeper(
+    context: Arc<Mutex<TracingContext>>,
+    period: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Ok(mut context) = context.lock() {
+                context.sweep_expired_spans();
+            }
+        }
+    })
+}
+
+/// Span which is finalized into its context when it goes out of scope.
+/// Child spans are created through `context()`, so they are always finalized
+/// before their parent.
+pub struct SpanGuard<'a> {
+    context: &'a mut TracingContext,
+    span: Option<Box<Span>>,
+}
+
+impl<'a> SpanGuard<'a> {
+    fn new(context: &'a mut TracingContext, span: Box<Span>) -> Self {
+        SpanGuard {
+            context,
+            span: Some(span),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        self.span.as_ref().unwrap()
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        self.span.as_mut().unwrap()
+    }
+
+    pub fn context(&mut self) -> &mut TracingContext {
+        self.context
+    }
+}
+
+impl<'a> Drop for SpanGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(span) = self.span.take() {
+            self.context.finalize_span(span);
         }
     }
 }
@@ -0,0 +1,37 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::context::trace_context::TracingContext;
+
+/// Field of `tracing::Span` which holds the trace ID.
+pub const TRACE_ID_FIELD: &str = "sw.trace_id";
+
+/// Field of `tracing::Span` which holds the segment ID.
+pub const SEGMENT_ID_FIELD: &str = "sw.segment_id";
+
+/// Record the trace ID and the segment ID of `context` onto `span`, so that logs
+/// and spans of `tracing` can be correlated with the trace. `tracing` only
+/// records the fields declared on span creation, like
+/// `info_span!("request", sw.trace_id = field::Empty, sw.segment_id = field::Empty)`.
+pub fn record_context(span: &tracing::Span, context: &TracingContext) {
+    span.record(TRACE_ID_FIELD, &context.trace_id.as_str());
+    span.record(SEGMENT_ID_FIELD, &context.trace_segment_id.as_str());
+}
+
+/// Record the IDs of `context` onto the current `tracing::Span`.
+pub fn record_context_on_current_span(context: &TracingContext) {
+    record_context(&tracing::Span::current(), context);
+}
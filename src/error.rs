@@ -0,0 +1,64 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::common::components::ComponentLoadError;
+use crate::context::trace_context::SegmentValidationError;
+use crate::reporter::reporter_trait::ReporterError;
+
+/// Error of decoding the propagated headers, like `sw8` and `b3`.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{0}")]
+pub struct DecodeError(pub &'static str);
+
+/// Error of this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    /// The tracing context is used in the wrong way, e.g. creating an exit span
+    /// before the entry span.
+    #[error("{0}")]
+    Context(&'static str),
+    #[error(transparent)]
+    Reporter(#[from] ReporterError),
+    #[error("failed to connect: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    /// The spool failed to persist or acknowledge segments.
+    #[error("spool failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Config(#[from] ComponentLoadError),
+    #[error("invalid segment: {0:?}")]
+    Validation(Vec<SegmentValidationError>),
+    /// Some of the reporters which share the segment failed, like the ones of
+    /// `TeeReporter`. It holds all of the errors.
+    #[error("{} reporters failed: {:?}", .0.len(), .0)]
+    Reporters(Vec<Error>),
+}
+
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Reporter(ReporterError::Backend(status))
+    }
+}
+
+impl From<Vec<SegmentValidationError>> for Error {
+    fn from(errors: Vec<SegmentValidationError>) -> Self {
+        Error::Validation(errors)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -20,6 +20,21 @@ pub mod skywalking_proto {
     }
 }
 
+/// Proto types which are commonly needed to use this crate.
+pub mod proto {
+    pub use crate::skywalking_proto::v3::{
+        KeyStringValuePair, Log, RefType, SegmentObject, SegmentReference, SpanLayer, SpanObject,
+        SpanType,
+    };
+}
+
 pub mod common;
 pub mod context;
+pub mod error;
 pub mod reporter;
+
+pub use error::{Error, Result};
+
+// Used by the exported macros, so that users don't need to depend on `log`.
+#[doc(hidden)]
+pub use log as __log;
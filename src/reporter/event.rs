@@ -0,0 +1,99 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::common::random_generator::RandomGenerator;
+use crate::error::Result;
+use crate::skywalking_proto::v3::event_service_client::EventServiceClient;
+use crate::skywalking_proto::v3::{Event, Source, Type};
+use std::collections::HashMap;
+use tonic::transport::Channel;
+
+pub type EventReporterClient = EventServiceClient<Channel>;
+
+/// Build an event which will be reported to OAP via `EventService`.
+/// Events express discrete happenings on the service like deployments or
+/// configuration changes.
+#[allow(clippy::too_many_arguments)]
+pub fn new_event(
+    service_name: &str,
+    instance_name: &str,
+    name: &str,
+    event_type: Type,
+    message: &str,
+    parameters: HashMap<String, String>,
+    start_time: i64,
+    end_time: i64,
+) -> Event {
+    Event {
+        uuid: RandomGenerator::generate(),
+        source: Some(Source {
+            service: service_name.to_string(),
+            service_instance: instance_name.to_string(),
+            ..Default::default()
+        }),
+        name: name.to_string(),
+        r#type: event_type as i32,
+        message: message.to_string(),
+        parameters,
+        start_time,
+        end_time,
+        ..Default::default()
+    }
+}
+
+pub struct EventReporter {
+    client: EventReporterClient,
+    service: String,
+    service_instance: String,
+}
+
+impl EventReporter {
+    pub fn new(client: EventReporterClient, service_name: &str, instance_name: &str) -> Self {
+        EventReporter {
+            client,
+            service: service_name.to_string(),
+            service_instance: instance_name.to_string(),
+        }
+    }
+
+    pub async fn connect(address: String, service_name: &str, instance_name: &str) -> Result<Self> {
+        let client = EventReporterClient::connect(address).await?;
+        Ok(EventReporter::new(client, service_name, instance_name))
+    }
+
+    /// Send an event to OAP. If the event doesn't have its source, the service
+    /// and instance of this reporter will be used.
+    pub async fn report_event(&mut self, mut event: Event) -> Result<()> {
+        if event.uuid.is_empty() {
+            event.uuid = RandomGenerator::generate();
+        }
+        if event.source.is_none() {
+            event.source = Some(Source {
+                service: self.service.clone(),
+                service_instance: self.service_instance.clone(),
+                ..Default::default()
+            });
+        }
+
+        let stream = async_stream::stream! {
+            yield event;
+        };
+        match self.client.collect(stream).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
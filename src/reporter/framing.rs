@@ -0,0 +1,106 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use prost::Message;
+use std::io::{self, Read, Write};
+
+/// Length of the frame header, which is the length of the body as u32 little endian.
+const FRAME_HEADER_LENGTH: usize = 4;
+
+/// Encode `message` into a frame, which consists of the length of the protobuf
+/// encoded message as u32 little endian followed by the message.
+pub fn encode_frame<M: Message>(message: &M) -> io::Result<Vec<u8>> {
+    let len = message.encoded_len();
+    let mut buf = Vec::with_capacity(FRAME_HEADER_LENGTH + len);
+    buf.extend_from_slice(&(len as u32).to_le_bytes());
+    message
+        .encode(&mut buf)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(buf)
+}
+
+/// Write `message` as a frame with a single `write_all`, so that frames of
+/// concurrent writers appending to the same file don't interleave.
+pub fn write_frame<W: Write, M: Message>(writer: &mut W, message: &M) -> io::Result<()> {
+    writer.write_all(&encode_frame(message)?)
+}
+
+/// Reader of frames written by `write_frame`. The writer may have crashed in the
+/// middle of the last frame, so the truncated tail is treated as the end of the
+/// frames instead of an error.
+pub struct FrameReader<R> {
+    reader: R,
+    truncated: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        FrameReader {
+            reader,
+            truncated: false,
+        }
+    }
+
+    /// Read the body of the next frame. It returns `None` at the end of the
+    /// frames, including the truncated tail.
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut header = [0u8; FRAME_HEADER_LENGTH];
+        let read = self.read_full(&mut header)?;
+        if read < FRAME_HEADER_LENGTH {
+            self.truncated = read != 0;
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes(header) as usize;
+        let mut body = vec![0u8; len];
+        if self.read_full(&mut body)? < len {
+            self.truncated = true;
+            return Ok(None);
+        }
+        Ok(Some(body))
+    }
+
+    /// Read all the complete frames and decode them as `M`.
+    pub fn read_messages<M: Message + Default>(&mut self) -> io::Result<Vec<M>> {
+        let mut messages = Vec::new();
+        while let Some(body) = self.read_frame()? {
+            let message =
+                M::decode(&body[..]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    /// Whether the last frame was truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Fill `buf` as much as possible. It returns the number of bytes read,
+    /// which is less than the length of `buf` only at EOF.
+    fn read_full(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
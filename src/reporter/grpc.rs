@@ -14,19 +14,51 @@
 // limitations under the License.
 //
 
+use crate::common::random_generator::RandomGenerator;
 use crate::context::trace_context::TracingContext;
+use crate::error::Result;
+use crate::reporter::reporter_trait::{AsyncReporter, Reporter as SegmentReporter, ReporterError};
+use crate::reporter::spool::Spool;
 use crate::skywalking_proto::v3::trace_segment_report_service_client::TraceSegmentReportServiceClient;
-use crate::skywalking_proto::v3::SegmentObject;
+use crate::skywalking_proto::v3::{
+    KeyStringValuePair, RefType, SegmentObject, SegmentReference, SpanObject,
+};
+use async_trait::async_trait;
+use prost::encoding::{encoded_len_varint, key_len};
+use prost::Message;
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::time::Instant;
 use tokio::sync::mpsc;
+use tonic::metadata::AsciiMetadataValue;
 use tonic::transport::Channel;
 
 pub type ReporterClient = TraceSegmentReportServiceClient<Channel>;
 
-async fn flush(client: &mut ReporterClient, context: SegmentObject) -> Result<(), tonic::Status> {
+async fn flush(
+    client: &mut ReporterClient,
+    segments: Vec<SegmentObject>,
+) -> Result<(), tonic::Status> {
+    flush_with_tenant(client, segments, None).await
+}
+
+async fn flush_with_tenant(
+    client: &mut ReporterClient,
+    segments: Vec<SegmentObject>,
+    tenant: Option<&AsciiMetadataValue>,
+) -> Result<(), tonic::Status> {
     let stream = async_stream::stream! {
-        yield context;
+        for segment in segments {
+            yield segment;
+        }
     };
-    match client.collect(stream).await {
+    let mut request = tonic::Request::new(stream);
+    if let Some(tenant) = tenant {
+        request
+            .metadata_mut()
+            .insert(TENANT_METADATA_KEY, tenant.clone());
+    }
+    match client.collect(request).await {
         Ok(_) => Ok(()),
         Err(e) => Err(e),
     }
@@ -41,7 +73,7 @@ impl Reporter {
         let mut reporter = ReporterClient::connect(address).await.unwrap();
         tokio::spawn(async move {
             while let Some(message) = rx.recv().await {
-                flush(&mut reporter, message.convert_segment_object())
+                flush(&mut reporter, vec![message.convert_segment_object()])
                     .await
                     .unwrap();
             }
@@ -49,3 +81,524 @@ impl Reporter {
         tx
     }
 }
+
+const DEFAULT_QUEUE_CAPACITY: usize = 32;
+
+/// tonic rejects messages larger than 4MB by default.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Field tag of `spans` in `SegmentObject`.
+const SPANS_FIELD_TAG: u32 = 3;
+
+/// Encoded size of `span` as an element of `SegmentObject::spans`, including
+/// the field tag and the length prefix.
+fn encoded_span_len(span: &SpanObject) -> usize {
+    let len = span.encoded_len();
+    key_len(SPANS_FIELD_TAG) + encoded_len_varint(len as u64) + len
+}
+
+/// Split the segment whose encoded size exceeds `max_message_size` into
+/// segments which share the trace ID. Split segments are marked as size limited.
+/// The first span of every split segment but the first one has a cross thread
+/// reference to its parent span in the original segment, so that the pieces
+/// are linked. A span larger than `max_message_size` can't be split, so it is
+/// sent alone.
+pub fn split_segment(mut segment: SegmentObject, max_message_size: usize) -> Vec<SegmentObject> {
+    if segment.encoded_len() <= max_message_size {
+        return vec![segment];
+    }
+
+    let spans = std::mem::take(&mut segment.spans);
+    segment.is_size_limited = true;
+    let base_size = segment.encoded_len();
+    let endpoint = spans
+        .first()
+        .map(|span| span.operation_name.clone())
+        .unwrap_or_default();
+
+    let mut chunks = Vec::<Vec<SpanObject>>::new();
+    let mut chunk = Vec::<SpanObject>::new();
+    let mut chunk_size = base_size;
+    for mut span in spans {
+        let mut span_size = encoded_span_len(&span);
+        if !chunk.is_empty() && chunk_size + span_size > max_message_size {
+            chunks.push(chunk);
+            chunk = Vec::new();
+            chunk_size = base_size;
+            span.refs
+                .push(split_reference(&segment, span.parent_span_id, &endpoint));
+            span_size = encoded_span_len(&span);
+        }
+        chunk_size += span_size;
+        chunk.push(span);
+    }
+    chunks.push(chunk);
+
+    // The first chunk keeps the original segment ID.
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, spans)| {
+            let mut split = segment.clone();
+            split.spans = spans;
+            if i != 0 {
+                split.trace_segment_id = RandomGenerator::generate();
+            }
+            split
+        })
+        .collect()
+}
+
+/// Reference from a split segment to the span `parent_span_id` of the original
+/// segment, which keeps the first split.
+fn split_reference(
+    segment: &SegmentObject,
+    parent_span_id: i32,
+    endpoint: &str,
+) -> SegmentReference {
+    SegmentReference {
+        ref_type: RefType::CrossThread as i32,
+        trace_id: segment.trace_id.clone(),
+        parent_trace_segment_id: segment.trace_segment_id.clone(),
+        parent_span_id: parent_span_id.max(0),
+        parent_service: segment.service.clone(),
+        parent_service_instance: segment.service_instance.clone(),
+        parent_endpoint: endpoint.to_string(),
+        ..Default::default()
+    }
+}
+
+/// gRPC metadata which multi-tenant OAP uses to identify the tenant.
+pub const TENANT_METADATA_KEY: &str = "tenant";
+
+/// Least recently reported segment keys. The oldest key is evicted when it
+/// exceeds the capacity.
+struct RecentSegments {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+    capacity: usize,
+}
+
+impl RecentSegments {
+    fn new(capacity: usize) -> Self {
+        RecentSegments {
+            order: VecDeque::with_capacity(capacity),
+            ids: HashSet::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remember the key. It returns false if it has been seen recently.
+    fn insert(&mut self, id: &str) -> bool {
+        if self.ids.contains(id) {
+            return false;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.to_string());
+        self.ids.insert(id.to_string());
+        true
+    }
+}
+
+/// Key of the segment for the dedup, made of its ID and the IDs of its spans.
+fn dedup_key(segment: &SegmentObject) -> String {
+    let mut key = segment.trace_segment_id.clone();
+    for span in segment.spans.iter() {
+        key.push_str(&format!("/{}", span.span_id));
+    }
+    key
+}
+
+/// Callback invoked when the queue occupancy reaches `fraction` of its capacity.
+/// It is invoked once per crossing, and armed again when the occupancy goes
+/// below the threshold.
+struct HighWatermark {
+    fraction: f64,
+    callback: Box<dyn Fn(usize) + Send + Sync>,
+    raised: bool,
+}
+
+impl HighWatermark {
+    fn threshold(&self, capacity: usize) -> usize {
+        ((capacity as f64 * self.fraction).ceil() as usize).max(1)
+    }
+}
+
+/// Token bucket which allows `rate` segments per second. It can burst up to
+/// `rate` segments.
+struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: usize) -> Self {
+        RateLimiter {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// How the reporter treats spans which were never closed (`end_time == 0`) or
+/// end before they start. They corrupt the aggregation of OAP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidSpanPolicy {
+    /// Remove invalid spans from the segment.
+    Drop,
+    /// Mark invalid spans as errored with the `invalid.duration` tag.
+    Flag,
+    /// Send invalid spans as they are.
+    Pass,
+}
+
+/// Tag which is added to the invalid spans on `InvalidSpanPolicy::Flag`.
+pub const INVALID_DURATION_TAG: &str = "invalid.duration";
+
+fn is_invalid_span(span: &SpanObject) -> bool {
+    span.end_time == 0 || span.end_time < span.start_time
+}
+
+/// Reporter which queues segments and sends them to OAP on `flush()`.
+pub struct GrpcReporter {
+    client: ReporterClient,
+    queue: VecDeque<SegmentObject>,
+    capacity: usize,
+    spool: Option<Spool>,
+    spool_backlog: VecDeque<SegmentObject>,
+    tenant: Option<AsciiMetadataValue>,
+    recent_segments: Option<RecentSegments>,
+    max_message_size: usize,
+    high_watermark: Option<HighWatermark>,
+    invalid_span_policy: InvalidSpanPolicy,
+    invalid_spans: usize,
+    flush_every: Option<usize>,
+    enqueued_since_flush: usize,
+    rate_limiter: Option<RateLimiter>,
+    rate_limited: usize,
+    transform: Option<Box<dyn Fn(SegmentObject) -> Option<SegmentObject> + Send + Sync>>,
+}
+
+impl GrpcReporter {
+    pub fn new(client: ReporterClient) -> Self {
+        GrpcReporter {
+            client,
+            queue: VecDeque::with_capacity(DEFAULT_QUEUE_CAPACITY),
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            spool: None,
+            spool_backlog: VecDeque::new(),
+            tenant: None,
+            recent_segments: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            high_watermark: None,
+            invalid_span_policy: InvalidSpanPolicy::Pass,
+            invalid_spans: 0,
+            flush_every: None,
+            enqueued_since_flush: 0,
+            rate_limiter: None,
+            rate_limited: 0,
+            transform: None,
+        }
+    }
+
+    pub async fn connect(address: String) -> Result<Self> {
+        let client = ReporterClient::connect(address).await?;
+        Ok(GrpcReporter::new(client))
+    }
+
+    /// Connect to the collector listening on the Unix domain socket at `path`,
+    /// like a sidecar on the same host.
+    #[cfg(all(feature = "uds", unix))]
+    pub async fn connect_uds<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        // The URI is required by tonic, but it is never used by the connector.
+        let channel = tonic::transport::Endpoint::from_static("http://[::]:11800")
+            .connect_with_connector(tower::service_fn(move |_: tonic::transport::Uri| {
+                tokio::net::UnixStream::connect(path.clone())
+            }))
+            .await?;
+        Ok(GrpcReporter::new(ReporterClient::new(channel)))
+    }
+
+    /// The maximum number of segments which can be queued until `flush()`.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Enable the write-ahead spool. Every reported segment is appended to the
+    /// spool before it is queued, and it is acknowledged once delivered.
+    /// Segments which were left in the spool by the previous process are
+    /// recovered into the queue, up to the capacity. So `with_capacity` should
+    /// be called before this. The rest of them stay in the spool, and fill the
+    /// queue again once it is delivered.
+    pub fn with_spool(mut self, spool: Spool) -> io::Result<Self> {
+        let room = self.capacity.saturating_sub(self.queue.len());
+        let mut pending = spool.pending()?.into_iter();
+        self.queue.extend(pending.by_ref().take(room));
+        self.spool_backlog.extend(pending);
+        self.spool = Some(spool);
+        self.check_high_watermark();
+        Ok(self)
+    }
+
+    /// Attach `tenant` metadata to every `collect` request. It fails if
+    /// `tenant` can't be a gRPC metadata value.
+    pub fn with_tenant(mut self, tenant: String) -> Result<Self> {
+        let value = tenant
+            .parse()
+            .map_err(|_| ReporterError::InvalidTenant(tenant))?;
+        self.tenant = Some(value);
+        Ok(self)
+    }
+
+    /// Drop segments whose `trace_segment_id` and span IDs are the same as one of
+    /// the last `window` reported segments. Retries and the spool may produce the
+    /// same segment more than once. Pieces of a segment reported by
+    /// `TracingContext::report_partial` share the ID but not the spans, so they
+    /// are all kept.
+    pub fn with_dedup(mut self, window: usize) -> Self {
+        self.recent_segments = Some(RecentSegments::new(window));
+        self
+    }
+
+    /// Segments larger than `max_message_size` bytes are split into several
+    /// segments on `flush()`. It should be the same as the limit of the collector.
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Call `callback` with the queue occupancy when it reaches `fraction` of the
+    /// capacity, so that operators can log, alert or shed load before segments
+    /// are dropped.
+    pub fn on_high_watermark<F>(mut self, fraction: f64, callback: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.high_watermark = Some(HighWatermark {
+            fraction,
+            callback: Box::new(callback),
+            raised: false,
+        });
+        self.check_high_watermark();
+        self
+    }
+
+    fn check_high_watermark(&mut self) {
+        let occupancy = self.queue.len();
+        let capacity = self.capacity;
+        if let Some(high_watermark) = self.high_watermark.as_mut() {
+            if occupancy < high_watermark.threshold(capacity) {
+                high_watermark.raised = false;
+            } else if !high_watermark.raised {
+                high_watermark.raised = true;
+                (high_watermark.callback)(occupancy);
+            }
+        }
+    }
+
+    /// Validate spans of reported segments with `policy`. Invalid spans are
+    /// passed through by default.
+    pub fn with_invalid_span_policy(mut self, policy: InvalidSpanPolicy) -> Self {
+        self.invalid_span_policy = policy;
+        self
+    }
+
+    /// The number of invalid spans which have been reported, regardless of
+    /// the policy.
+    pub fn invalid_span_count(&self) -> usize {
+        self.invalid_spans
+    }
+
+    fn validate_spans(&mut self, segment: &mut SegmentObject) {
+        let invalid_spans = segment
+            .spans
+            .iter()
+            .filter(|span| is_invalid_span(span))
+            .count();
+        if invalid_spans == 0 {
+            return;
+        }
+        self.invalid_spans += invalid_spans;
+
+        match self.invalid_span_policy {
+            InvalidSpanPolicy::Drop => segment.spans.retain(|span| !is_invalid_span(span)),
+            InvalidSpanPolicy::Flag => {
+                for span in segment
+                    .spans
+                    .iter_mut()
+                    .filter(|span| is_invalid_span(span))
+                {
+                    span.is_error = true;
+                    span.tags.push(KeyStringValuePair {
+                        key: INVALID_DURATION_TAG.to_string(),
+                        value: "true".to_string(),
+                    });
+                }
+            }
+            InvalidSpanPolicy::Pass => {}
+        }
+    }
+
+    /// Flush only after every `count` segments are enqueued, instead of every
+    /// segment, when it is used as `AsyncReporter`.
+    pub fn flush_every(mut self, count: usize) -> Self {
+        self.flush_every = Some(count);
+        self
+    }
+
+    /// Accept at most `segments_per_second` segments per second to protect the
+    /// shared collector. Excess segments are dropped and counted.
+    pub fn with_rate_limit(mut self, segments_per_second: usize) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(segments_per_second));
+        self
+    }
+
+    /// Transform every segment before it is queued, e.g. to scrub PII from tags.
+    /// The segment is dropped if `transform` returns `None`.
+    pub fn with_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(SegmentObject) -> Option<SegmentObject> + Send + Sync + 'static,
+    {
+        self.transform = Some(Box::new(transform));
+        self
+    }
+
+    /// The number of segments which have been dropped by the rate limit.
+    pub fn rate_limited_count(&self) -> usize {
+        self.rate_limited
+    }
+
+    /// The number of segments waiting for `flush()`.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Send all queued segments in a single `collect` call.
+    /// Segments stay in the queue if they failed to be sent.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.send_queue().await
+    }
+
+    async fn send_queue(&mut self) -> Result<()> {
+        if self.queue.is_empty() {
+            return Ok(());
+        }
+
+        let max_message_size = self.max_message_size;
+        let segments: Vec<SegmentObject> = self
+            .queue
+            .iter()
+            .cloned()
+            .flat_map(|segment| split_segment(segment, max_message_size))
+            .collect();
+        flush_with_tenant(&mut self.client, segments, self.tenant.as_ref()).await?;
+        self.queue.clear();
+        self.enqueued_since_flush = 0;
+
+        if let Some(spool) = self.spool.as_mut() {
+            // Acknowledging clears the whole spool, so the recovered segments
+            // which haven't been queued yet are appended again.
+            spool.acknowledge()?;
+            for segment in self.spool_backlog.iter() {
+                spool.append(segment)?;
+            }
+            let room = self.capacity.min(self.spool_backlog.len());
+            self.queue.extend(self.spool_backlog.drain(..room));
+        }
+        self.check_high_watermark();
+        Ok(())
+    }
+
+    /// Send `segments` along with the queued ones in a single `collect` call.
+    /// It is useful for workers which drain many finished contexts at once.
+    /// Every segment is admitted in the same way as `report`, e.g. by the rate
+    /// limit and the dedup. The queue is sent early if it gets full.
+    pub async fn report_all(&mut self, segments: Vec<SegmentObject>) -> Result<()> {
+        for segment in segments {
+            if self.queue.len() >= self.capacity {
+                self.send_queue().await?;
+            }
+            SegmentReporter::report(self, segment)?;
+        }
+        self.send_queue().await
+    }
+}
+
+impl SegmentReporter for GrpcReporter {
+    fn report(&mut self, mut segment: SegmentObject) -> Result<()> {
+        if self.queue.len() >= self.capacity {
+            return Err(ReporterError::QueueFull.into());
+        }
+
+        if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+            if !rate_limiter.try_acquire() {
+                self.rate_limited += 1;
+                return Ok(());
+            }
+        }
+
+        if let Some(recent_segments) = self.recent_segments.as_mut() {
+            if !recent_segments.insert(&dedup_key(&segment)) {
+                return Ok(());
+            }
+        }
+
+        // The transform is applied before the spool, so that scrubbed data is
+        // never persisted.
+        if let Some(transform) = self.transform.as_ref() {
+            segment = match transform(segment) {
+                Some(segment) => segment,
+                None => return Ok(()),
+            };
+        }
+        self.validate_spans(&mut segment);
+
+        // The spool is best effort. Failing to persist a segment must not
+        // prevent it from being delivered.
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.append(&segment) {
+                log::warn!("failed to append the segment to the spool: {}", e);
+            }
+        }
+        self.queue.push_back(segment);
+        self.enqueued_since_flush += 1;
+
+        self.check_high_watermark();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncReporter for GrpcReporter {
+    async fn report(&mut self, segment: SegmentObject) -> Result<()> {
+        SegmentReporter::report(self, segment)?;
+        if let Some(count) = self.flush_every {
+            if self.enqueued_since_flush < count {
+                return Ok(());
+            }
+        }
+        self.send_queue().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::error::Result;
+use crate::skywalking_proto::v3::management_service_client::ManagementServiceClient;
+use crate::skywalking_proto::v3::InstancePingPkg;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tonic::transport::Channel;
+
+/// Reporter which sends keepalive pings to OAP. A single reporter can track
+/// several co-located instances, and each of them is pinged independently.
+pub struct KeepAliveReporter {
+    client: ManagementServiceClient<Channel>,
+    instances: Vec<(String, String)>,
+}
+
+impl KeepAliveReporter {
+    pub fn new(client: ManagementServiceClient<Channel>) -> Self {
+        KeepAliveReporter {
+            client,
+            instances: Vec::new(),
+        }
+    }
+
+    pub async fn connect(address: String) -> Result<Self> {
+        let client = ManagementServiceClient::connect(address).await?;
+        Ok(KeepAliveReporter::new(client))
+    }
+
+    /// Start tracking an instance. Registering the same instance twice has no effect.
+    pub fn register_instance(&mut self, service_name: &str, instance_name: &str) {
+        if !self.is_registered(service_name, instance_name) {
+            self.instances
+                .push((service_name.to_string(), instance_name.to_string()));
+        }
+    }
+
+    /// Stop tracking an instance. It returns `false` if it was not registered.
+    pub fn unregister_instance(&mut self, service_name: &str, instance_name: &str) -> bool {
+        let len = self.instances.len();
+        self.instances
+            .retain(|(service, instance)| service != service_name || instance != instance_name);
+        self.instances.len() != len
+    }
+
+    pub fn is_registered(&self, service_name: &str, instance_name: &str) -> bool {
+        self.instances
+            .iter()
+            .any(|(service, instance)| service == service_name && instance == instance_name)
+    }
+
+    pub fn instances(&self) -> &[(String, String)] {
+        &self.instances
+    }
+
+    /// Send a keepalive ping for every registered instance. All instances are
+    /// pinged even if some of them fail, and the first error is returned.
+    pub async fn keep_alive(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for (service, instance) in self.instances.iter() {
+            let ping = InstancePingPkg {
+                service: service.clone(),
+                service_instance: instance.clone(),
+                ..Default::default()
+            };
+            if let Err(status) = self.client.keep_alive(ping).await {
+                if result.is_ok() {
+                    result = Err(status.into());
+                }
+            }
+        }
+        result
+    }
+
+    /// Ping all registered instances every `interval` in a background task.
+    pub fn spawn(mut self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(status) = self.keep_alive().await {
+                    log::warn!("failed to send keepalive: {}", status);
+                }
+            }
+        })
+    }
+}
@@ -14,5 +14,11 @@
 // limitations under the License.
 //
 
+pub mod event;
+pub mod framing;
 pub mod grpc;
+pub mod management;
+pub mod profile;
 pub mod reporter_trait;
+pub mod spool;
+pub mod tee;
@@ -0,0 +1,123 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::error::Result;
+use crate::skywalking_proto::v3::profile_task_client::ProfileTaskClient as ProfileTaskServiceClient;
+use crate::skywalking_proto::v3::{Command, ProfileTaskCommandQuery};
+use tonic::transport::Channel;
+
+/// Name of the command which OAP sends to start profiling.
+pub const PROFILE_TASK_COMMAND: &str = "ProfileTaskQuery";
+
+/// Profiling task which OAP requested to this instance.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProfileTaskCommand {
+    pub serial_number: String,
+    pub task_id: String,
+    pub endpoint_name: String,
+    /// Duration of the task in minutes.
+    pub duration: i32,
+    /// Only spans longer than this in milliseconds are profiled.
+    pub min_duration_threshold: i32,
+    /// Interval of thread dumps in milliseconds.
+    pub dump_period: i32,
+    pub max_sampling_count: i32,
+    pub start_time: i64,
+    pub create_time: i64,
+}
+
+impl ProfileTaskCommand {
+    /// Parse the command. It returns `None` if the command is not a profiling
+    /// task or some arguments are missing.
+    pub fn from_command(command: &Command) -> Option<Self> {
+        if command.command != PROFILE_TASK_COMMAND {
+            return None;
+        }
+
+        let arg = |key: &str| {
+            command
+                .args
+                .iter()
+                .find(|arg| arg.key == key)
+                .map(|arg| arg.value.as_str())
+        };
+        Some(ProfileTaskCommand {
+            serial_number: arg("SerialNumber")?.to_string(),
+            task_id: arg("TaskId")?.to_string(),
+            endpoint_name: arg("EndpointName")?.to_string(),
+            duration: arg("Duration")?.parse().ok()?,
+            min_duration_threshold: arg("MinDurationThreshold")?.parse().ok()?,
+            dump_period: arg("DumpPeriod")?.parse().ok()?,
+            max_sampling_count: arg("MaxSamplingCount")?.parse().ok()?,
+            start_time: arg("StartTime")?.parse().ok()?,
+            create_time: arg("CreateTime")?.parse().ok()?,
+        })
+    }
+}
+
+/// Client which fetches profiling tasks of this instance from OAP.
+pub struct ProfileTaskClient {
+    client: ProfileTaskServiceClient<Channel>,
+    service: String,
+    service_instance: String,
+    last_command_time: i64,
+}
+
+impl ProfileTaskClient {
+    pub fn new(
+        client: ProfileTaskServiceClient<Channel>,
+        service_name: &str,
+        instance_name: &str,
+    ) -> Self {
+        ProfileTaskClient {
+            client,
+            service: service_name.to_string(),
+            service_instance: instance_name.to_string(),
+            last_command_time: 0,
+        }
+    }
+
+    pub async fn connect(address: String, service_name: &str, instance_name: &str) -> Result<Self> {
+        let client = ProfileTaskServiceClient::connect(address).await?;
+        Ok(ProfileTaskClient::new(client, service_name, instance_name))
+    }
+
+    /// Fetch tasks which have been created since the last call.
+    pub async fn get_profile_task_commands(&mut self) -> Result<Vec<ProfileTaskCommand>> {
+        let query = ProfileTaskCommandQuery {
+            service: self.service.clone(),
+            service_instance: self.service_instance.clone(),
+            last_command_time: self.last_command_time,
+        };
+        let commands = self
+            .client
+            .get_profile_task_commands(query)
+            .await?
+            .into_inner();
+
+        let tasks: Vec<ProfileTaskCommand> = commands
+            .commands
+            .iter()
+            .filter_map(ProfileTaskCommand::from_command)
+            .collect();
+        for task in tasks.iter() {
+            if task.create_time > self.last_command_time {
+                self.last_command_time = task.create_time;
+            }
+        }
+        Ok(tasks)
+    }
+}
@@ -14,13 +14,40 @@
 // limitations under the License.
 //
 
+use crate::error::Result;
 use crate::skywalking_proto::v3::SegmentObject;
-use tokio::sync::mpsc;
+use async_trait::async_trait;
 
 pub trait Reporter {
     /// Send report
-    fn report(
-        &mut self,
-        ctx: SegmentObject,
-    ) -> Result<(), mpsc::error::TrySendError<SegmentObject>>;
+    fn report(&mut self, ctx: SegmentObject) -> Result<()>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReporterError {
+    /// The reporter can't accept segments anymore until it is flushed.
+    #[error("reporter queue is full.")]
+    QueueFull,
+    /// The reporter doesn't accept segments anymore, e.g. its channel is closed.
+    #[error("reporter is closed.")]
+    Closed,
+    /// The tenant name can't be sent as gRPC metadata.
+    #[error("invalid tenant name: {0}")]
+    InvalidTenant(String),
+    /// The backend failed to receive segments.
+    #[error("failed to report segments: {0}")]
+    Backend(#[from] tonic::Status),
+}
+
+/// Reporter whose backend should be awaited, like HTTP exporters.
+#[async_trait]
+pub trait AsyncReporter {
+    /// Send report and wait until the backend receives it.
+    async fn report(&mut self, segment: SegmentObject) -> Result<()>;
+}
+
+/// Reporter of meters, like the histogram of operation latency.
+pub trait MeterReporter {
+    /// Record `value` into the meter `name` labeled with `labels`.
+    fn record(&self, name: &str, value: f64, labels: &[(String, String)]);
 }
@@ -0,0 +1,59 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::reporter::framing::{write_frame, FrameReader};
+use crate::skywalking_proto::v3::SegmentObject;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Write-ahead spool of segments which haven't been delivered to OAP yet.
+/// Each segment is stored as a frame of `framing` module.
+pub struct Spool {
+    path: PathBuf,
+    file: File,
+}
+
+impl Spool {
+    /// Open the spool placed on `path`. Segments written by the previous process
+    /// are kept and can be read by `pending()`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        Ok(Spool { path, file })
+    }
+
+    pub fn append(&mut self, segment: &SegmentObject) -> io::Result<()> {
+        write_frame(&mut self.file, segment)?;
+        self.file.sync_data()
+    }
+
+    /// Read all segments which haven't been acknowledged yet. The segment which
+    /// was being appended when the process crashed is truncated, so it is skipped.
+    pub fn pending(&self) -> io::Result<Vec<SegmentObject>> {
+        let file = io::BufReader::new(File::open(&self.path)?);
+        FrameReader::new(file).read_messages()
+    }
+
+    /// Mark all segments appended so far as delivered.
+    pub fn acknowledge(&mut self) -> io::Result<()> {
+        self.file.set_len(0)
+    }
+}
@@ -0,0 +1,47 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::error::{Error, Result};
+use crate::reporter::reporter_trait::Reporter;
+use crate::skywalking_proto::v3::SegmentObject;
+
+/// Reporter which forwards every segment to all of the reporters, e.g. to the
+/// production backend and a reporter for verification during migrations.
+pub struct TeeReporter {
+    reporters: Vec<Box<dyn Reporter + Send>>,
+}
+
+impl TeeReporter {
+    pub fn new(reporters: Vec<Box<dyn Reporter + Send>>) -> Self {
+        TeeReporter { reporters }
+    }
+}
+
+impl Reporter for TeeReporter {
+    /// The segment is forwarded to all of the reporters even if some of them
+    /// fail. The errors of all the failed reporters are returned together.
+    fn report(&mut self, segment: SegmentObject) -> Result<()> {
+        let errors: Vec<Error> = self
+            .reporters
+            .iter_mut()
+            .filter_map(|reporter| reporter.report(segment.clone()).err())
+            .collect();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(Error::Reporters(errors))
+    }
+}
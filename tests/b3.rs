@@ -0,0 +1,104 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::context::propagation::b3::{decode_b3, decode_b3_single};
+
+#[test]
+fn decode_multi_headers() {
+    let res = decode_b3(
+        "463ac35c9f6413ad48485a3953bb6124",
+        "a2fb4a1d1a96d312",
+        Some("0020000000000001"),
+        Some("1"),
+    )
+    .unwrap();
+
+    assert_eq!(res.do_sample, true);
+    assert_eq!(res.parent_trace_id, "463ac35c9f6413ad48485a3953bb6124");
+    assert_eq!(res.b3_span_id.as_deref(), Some("a2fb4a1d1a96d312"));
+    assert_eq!(res.b3_parent_span_id.as_deref(), Some("0020000000000001"));
+    assert_eq!(res.parent_trace_segment_id, "");
+    assert_eq!(res.parent_service, "");
+    assert_eq!(res.parent_service_instance, "");
+
+    let res = decode_b3("463ac35c9f6413ad", "A2FB4A1D1A96D312", None, Some("0")).unwrap();
+    assert_eq!(res.do_sample, false);
+    assert_eq!(res.b3_span_id.as_deref(), Some("a2fb4a1d1a96d312"));
+    assert_eq!(res.b3_parent_span_id, None);
+
+    let res = decode_b3("463ac35c9f6413ad", "a2fb4a1d1a96d312", None, None).unwrap();
+    assert_eq!(res.do_sample, true);
+}
+
+#[test]
+fn decode_invalid_multi_headers() {
+    assert_eq!(
+        decode_b3("xyz", "a2fb4a1d1a96d312", None, None).is_err(),
+        true
+    );
+    assert_eq!(
+        decode_b3("463ac35c9f6413ad", "a2fb", None, None).is_err(),
+        true
+    );
+    assert_eq!(
+        decode_b3("463ac35c9f6413ad", "a2fb4a1d1a96d312", Some("1"), None).is_err(),
+        true
+    );
+    assert_eq!(
+        decode_b3("463ac35c9f6413ad", "a2fb4a1d1a96d312", None, Some("yes")).is_err(),
+        true
+    );
+}
+
+#[test]
+fn decode_single_header() {
+    let res =
+        decode_b3_single("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90")
+            .unwrap();
+
+    assert_eq!(res.do_sample, true);
+    assert_eq!(res.parent_trace_id, "80f198ee56343ba864fe8b2a57d3eff7");
+    assert_eq!(res.b3_span_id.as_deref(), Some("e457b5a2e4d86bd1"));
+    assert_eq!(res.b3_parent_span_id.as_deref(), Some("05e3ac9a4f6e3b90"));
+
+    let res = decode_b3_single("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-0").unwrap();
+    assert_eq!(res.do_sample, false);
+
+    let res = decode_b3_single("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1").unwrap();
+    assert_eq!(res.do_sample, true);
+
+    assert_eq!(
+        decode_b3_single("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3-1").is_err(),
+        true
+    );
+}
+
+#[test]
+fn decode_sampling_only_single_header() {
+    let res = decode_b3_single("0").unwrap();
+    assert_eq!(res.do_sample, false);
+    assert_eq!(res.parent_trace_id, "");
+    assert_eq!(res.b3_span_id, None);
+
+    let res = decode_b3_single("d").unwrap();
+    assert_eq!(res.do_sample, true);
+    assert_eq!(res.parent_trace_id, "");
+
+    let res = decode_b3_single("1").unwrap();
+    assert_eq!(res.do_sample, true);
+
+    assert_eq!(decode_b3_single("yes").is_err(), true);
+}
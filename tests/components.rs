@@ -0,0 +1,104 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::common::components::{component_id, ComponentLoadError, ComponentRegistry};
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::trace_context::TracingContext;
+use std::sync::Arc;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+#[test]
+fn known_components() {
+    assert_eq!(component_id("httpclient"), Some(2));
+    assert_eq!(component_id("redis"), Some(7));
+    assert_eq!(component_id("grpc"), Some(23));
+    assert_eq!(component_id("GRPC"), Some(23));
+}
+
+#[test]
+fn unknown_component() {
+    assert_eq!(component_id("no-such-component"), None);
+}
+
+#[test]
+fn set_component_by_name() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _span1 = context.create_entry_span("op1").unwrap();
+    let mut span2 = context.create_exit_span("op2", "redis:6379").unwrap();
+    span2.set_component_id(component_id("redis").unwrap());
+    assert_eq!(span2.span_object().component_id, 7);
+}
+
+#[test]
+fn load_custom_components() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/component-libraries.yml"
+    );
+    let registry = ComponentRegistry::from_yaml_file(path).unwrap();
+    assert_eq!(registry.component_id("InHouseRPC"), Some(30001));
+    assert_eq!(registry.component_id("inhousecache"), Some(30002));
+    assert_eq!(registry.component_id("tomcat"), Some(1));
+    // Built-in components are still available.
+    assert_eq!(registry.component_id("grpc"), Some(23));
+    assert_eq!(registry.component_id("Component-Server-Mappings"), None);
+    assert_eq!(registry.component_id("no-such-component"), None);
+}
+
+#[test]
+fn install_custom_components() {
+    let registry = ComponentRegistry::from_yaml_str("InHouseKV:\n  id: 30003\n").unwrap();
+    assert_eq!(registry.install().is_ok(), true);
+    assert_eq!(component_id("inhousekv"), Some(30003));
+    assert_eq!(component_id("redis"), Some(7));
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _entry = context.create_entry_span("op1").unwrap();
+    let span = context
+        .create_cache_exit_span("kv:6379", "InHouseKV", "GET", "user:1")
+        .unwrap();
+    assert_eq!(span.span_object().component_id, 30003);
+
+    let registry = ComponentRegistry::from_yaml_str("InHouseKV:\n  id: 30004\n").unwrap();
+    assert_eq!(registry.install().is_err(), true);
+    assert_eq!(component_id("InHouseKV"), Some(30003));
+}
+
+#[test]
+fn invalid_component_libraries() {
+    let res = ComponentRegistry::from_yaml_str("InHouseRPC:\n  languages: Rust\n");
+    match res {
+        Err(ComponentLoadError::InvalidComponent(name)) => assert_eq!(name, "InHouseRPC"),
+        _ => panic!("component without id must be rejected."),
+    }
+
+    let res = ComponentRegistry::from_yaml_str("InHouseRPC: [");
+    assert_eq!(matches!(res, Err(ComponentLoadError::Parse(_))), true);
+
+    let res = ComponentRegistry::from_yaml_file("/no/such/component-libraries.yml");
+    assert_eq!(matches!(res, Err(ComponentLoadError::Io(_))), true);
+}
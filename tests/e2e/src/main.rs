@@ -20,16 +20,20 @@ async fn handle_ping(
     let mut context = TracingContext::default("producer", "node_0");
     let span = context.create_entry_span("/ping").unwrap();
     {
-        let span2 = context.create_exit_span("/pong", "consumer:8082").unwrap();
-        let header = encode_propagation(&context, "/pong", "consumer:8082");
-        let req = Request::builder()
-            .method(Method::GET)
-            .header("sw8", header)
-            .uri("http://consumer:8082/pong")
-            .body(Body::from(""))
-            .unwrap();
+        let span2 = context.create_local_span("call_consumer").unwrap();
+        {
+            let span3 = context.create_exit_span("/pong", "consumer:8082").unwrap();
+            let header = encode_propagation(&context, Some("/pong"), Some("consumer:8082"));
+            let req = Request::builder()
+                .method(Method::GET)
+                .header("sw8", header)
+                .uri("http://consumer:8082/pong")
+                .body(Body::from(""))
+                .unwrap();
 
-        client.request(req).await.unwrap();
+            client.request(req).await.unwrap();
+            context.finalize_span(span3);
+        }
         context.finalize_span(span2);
     }
     context.finalize_span(span);
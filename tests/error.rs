@@ -0,0 +1,94 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::propagation::decoder::decode_propagation;
+use rs2sky::context::trace_context::{SegmentValidationError, TracingContext};
+use rs2sky::error::DecodeError;
+use rs2sky::reporter::reporter_trait::ReporterError;
+use rs2sky::Error;
+use std::sync::Arc;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+fn continue_trace(sw8: &str) -> rs2sky::Result<TracingContext> {
+    let propagation = decode_propagation(sw8)?;
+    Ok(TracingContext::from_propagation_context_internal(
+        Arc::new(MockTimeFetcher {}),
+        "service",
+        "instance",
+        propagation,
+    ))
+}
+
+#[test]
+fn from_decode_error() {
+    let err = Error::from(DecodeError("failed to decode value."));
+    assert_eq!(matches!(err, Error::Decode(_)), true);
+    assert_eq!(err.to_string(), "failed to decode value.");
+
+    let err = continue_trace("1-invalid").err().unwrap();
+    assert_eq!(
+        matches!(
+            err,
+            Error::Decode(DecodeError(
+                "failed to parse propagation context: it must have 8 properties."
+            ))
+        ),
+        true
+    );
+
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(continue_trace(data).unwrap().trace_id, "1");
+}
+
+#[test]
+fn from_status() {
+    let err = Error::from(tonic::Status::unavailable("collector is down"));
+    match err {
+        Error::Reporter(ReporterError::Backend(status)) => {
+            assert_eq!(status.code(), tonic::Code::Unavailable);
+            assert_eq!(status.message(), "collector is down");
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn from_context_and_validation_error() {
+    let mut context =
+        TracingContext::default_internal(Arc::new(MockTimeFetcher {}), "service", "instance");
+    let err = context.create_exit_span("op", "peer").err().unwrap();
+    assert_eq!(matches!(err, Error::Context(_)), true);
+
+    let err = Error::from(vec![SegmentValidationError::SpanNotClosed(0)]);
+    assert_eq!(matches!(err, Error::Validation(_)), true);
+}
+
+#[test]
+fn from_io_error() {
+    let err = Error::from(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "disk is full",
+    ));
+    assert_eq!(matches!(err, Error::Io(_)), true);
+}
@@ -0,0 +1,53 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use prost::Message;
+use rs2sky::reporter::event::new_event;
+use rs2sky::skywalking_proto::v3::{Event, Type};
+use std::collections::HashMap;
+
+#[test]
+fn build_event() {
+    let mut parameters = HashMap::new();
+    parameters.insert("version".to_string(), "v1.2.0".to_string());
+
+    let event = new_event(
+        "service",
+        "instance",
+        "Upgrade",
+        Type::Error,
+        "upgrade failed",
+        parameters,
+        100,
+        200,
+    );
+
+    let mut buf = Vec::new();
+    event.encode(&mut buf).unwrap();
+    let decoded = Event::decode(&buf[..]).unwrap();
+
+    assert_eq!(decoded.uuid.is_empty(), false);
+    assert_eq!(decoded.name, "Upgrade");
+    assert_eq!(decoded.r#type, Type::Error as i32);
+    assert_eq!(decoded.message, "upgrade failed");
+    assert_eq!(decoded.parameters["version"], "v1.2.0");
+    assert_eq!(decoded.start_time, 100);
+    assert_eq!(decoded.end_time, 200);
+
+    let source = decoded.source.unwrap();
+    assert_eq!(source.service, "service");
+    assert_eq!(source.service_instance, "instance");
+}
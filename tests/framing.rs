@@ -0,0 +1,55 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::reporter::framing::{write_frame, FrameReader};
+use rs2sky::skywalking_proto::v3::SegmentObject;
+
+fn segment(segment_id: &str) -> SegmentObject {
+    SegmentObject {
+        trace_id: "trace".to_string(),
+        trace_segment_id: segment_id.to_string(),
+        service: "service".to_string(),
+        service_instance: "instance".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn read_frames_with_truncated_tail() {
+    let mut buf = Vec::new();
+    write_frame(&mut buf, &segment("1")).unwrap();
+    write_frame(&mut buf, &segment("2")).unwrap();
+    let complete = buf.len();
+    write_frame(&mut buf, &segment("3")).unwrap();
+
+    // The writer crashed in the middle of the body of the last frame.
+    buf.truncate(buf.len() - 3);
+    let mut reader = FrameReader::new(&buf[..]);
+    let segments: Vec<SegmentObject> = reader.read_messages().unwrap();
+    assert_eq!(segments, vec![segment("1"), segment("2")]);
+    assert_eq!(reader.is_truncated(), true);
+
+    // The writer crashed in the middle of the header of the last frame.
+    let mut reader = FrameReader::new(&buf[..complete + 2]);
+    let segments: Vec<SegmentObject> = reader.read_messages().unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(reader.is_truncated(), true);
+
+    let mut reader = FrameReader::new(&buf[..complete]);
+    let segments: Vec<SegmentObject> = reader.read_messages().unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(reader.is_truncated(), false);
+}
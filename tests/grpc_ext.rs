@@ -0,0 +1,66 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![cfg(feature = "grpc")]
+
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::propagation::grpc_ext::{extract_from_metadata, inject_into_metadata};
+use rs2sky::context::trace_context::TracingContext;
+use std::sync::Arc;
+use tonic::metadata::MetadataMap;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+#[test]
+fn inject_and_extract() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _span = context.create_entry_span("op1").unwrap();
+
+    let mut metadata = MetadataMap::new();
+    inject_into_metadata(
+        &mut metadata,
+        &context,
+        "/grpc.health.v1.Health/Check",
+        "consumer:50051",
+    );
+
+    let prop = extract_from_metadata(&metadata).unwrap().unwrap();
+    assert_eq!(prop.do_sample, true);
+    assert_eq!(prop.parent_trace_id, context.trace_id);
+    assert_eq!(prop.parent_trace_segment_id, context.trace_segment_id);
+    assert_eq!(prop.parent_span_id, 0);
+    assert_eq!(prop.parent_service, "service");
+    assert_eq!(prop.parent_service_instance, "instance");
+    assert_eq!(prop.destination_endpoint, "op1");
+    assert_eq!(prop.destination_address, "consumer:50051");
+}
+
+#[test]
+fn extract_absent_or_invalid() {
+    let mut metadata = MetadataMap::new();
+    assert_eq!(extract_from_metadata(&metadata).is_none(), true);
+
+    metadata.insert("sw8", "invalid".parse().unwrap());
+    assert_eq!(extract_from_metadata(&metadata).unwrap().is_err(), true);
+}
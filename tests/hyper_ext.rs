@@ -0,0 +1,55 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![cfg(feature = "hyper")]
+
+use hyper::{Body, Request};
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::propagation::hyper_ext::RequestTracingExt;
+use rs2sky::context::trace_context::TracingContext;
+use std::sync::Arc;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+#[test]
+fn inject_and_extract() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _span = context.create_entry_span("op1").unwrap();
+
+    let mut req = Request::builder()
+        .uri("http://consumer:8082/pong")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(req.extract_sw8().is_none(), true);
+
+    req.inject_sw8(&context, "/pong", "consumer:8082");
+    let prop = req.extract_sw8().unwrap();
+    assert_eq!(prop.do_sample, true);
+    assert_eq!(prop.parent_trace_id, context.trace_id);
+    assert_eq!(prop.parent_trace_segment_id, context.trace_segment_id);
+    assert_eq!(prop.parent_service, "service");
+    assert_eq!(prop.parent_service_instance, "instance");
+    assert_eq!(prop.destination_endpoint, "op1");
+    assert_eq!(prop.destination_address, "consumer:8082");
+}
@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::managed_context::ManagedContext;
+use rs2sky::context::trace_context::TracingContext;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+#[tokio::test]
+async fn report_on_drop() {
+    let (tx, mut rx) = mpsc::channel(1);
+    {
+        let time_fetcher = MockTimeFetcher {};
+        let context =
+            TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+        let mut context = ManagedContext::new(context, tx);
+
+        let mut entry = context.entry_guard("op1").unwrap();
+        entry.span_mut().add_tag(("hoge", "fuga"));
+        let _exit = entry.context().exit_guard("op2", "remote_peer").unwrap();
+    }
+
+    let context = rx.recv().await.unwrap();
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.service, "service");
+    assert_eq!(segment.spans.len(), 2);
+    assert_eq!(segment.spans[0].operation_name, "op1");
+    assert_eq!(segment.spans[0].end_time, 100);
+    assert_eq!(segment.spans[1].operation_name, "op2");
+    assert_eq!(segment.spans[1].end_time, 100);
+}
+
+#[tokio::test]
+async fn finalize_active_spans_on_drop() {
+    let (tx, mut rx) = mpsc::channel(1);
+    {
+        let time_fetcher = MockTimeFetcher {};
+        let context =
+            TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+        let mut context = ManagedContext::new(context, tx);
+
+        let _entry = context.create_entry_span("op1").unwrap();
+        let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+        context.finalize_span(exit);
+        let _local = context.create_local_span("op3").unwrap();
+    }
+
+    let context = rx.recv().await.unwrap();
+    assert_eq!(context.active_span_count(), 0);
+    let operation_names: Vec<String> = context
+        .spans
+        .iter()
+        .map(|span| span.span_object().operation_name.clone())
+        .collect();
+    // The innermost span is finalized first.
+    assert_eq!(operation_names, vec!["op2", "op3", "op1"]);
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 3);
+    assert_eq!(segment.spans[2].parent_span_id, 0);
+    assert_eq!(segment.spans[2].end_time, 100);
+}
+
+#[tokio::test]
+async fn skip_unsampled_context_on_drop() {
+    let (tx, mut rx) = mpsc::channel(1);
+    {
+        let time_fetcher = MockTimeFetcher {};
+        let mut context =
+            TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+        context.set_sampled(false);
+        let mut context = ManagedContext::new(context, tx);
+        let _entry = context.create_entry_span("op1").unwrap();
+    }
+
+    assert_eq!(rx.recv().await.is_none(), true);
+}
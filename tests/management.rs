@@ -0,0 +1,87 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::reporter::management::KeepAliveReporter;
+use rs2sky::skywalking_proto::v3::management_service_server::{
+    ManagementService, ManagementServiceServer,
+};
+use rs2sky::skywalking_proto::v3::{Commands, InstancePingPkg, InstanceProperties};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+#[derive(Default, Clone)]
+struct MockManagementService {
+    pings: Arc<Mutex<Vec<InstancePingPkg>>>,
+}
+
+#[tonic::async_trait]
+impl ManagementService for MockManagementService {
+    async fn report_instance_properties(
+        &self,
+        _request: Request<InstanceProperties>,
+    ) -> Result<Response<Commands>, Status> {
+        Ok(Response::new(Commands::default()))
+    }
+
+    async fn keep_alive(
+        &self,
+        request: Request<InstancePingPkg>,
+    ) -> Result<Response<Commands>, Status> {
+        self.pings.lock().unwrap().push(request.into_inner());
+        Ok(Response::new(Commands::default()))
+    }
+}
+
+#[tokio::test]
+async fn keep_alive_all_instances() {
+    let service = MockManagementService::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let server = service.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ManagementServiceServer::new(server))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+
+    let mut reporter = KeepAliveReporter::connect(address).await.unwrap();
+    reporter.register_instance("service", "instance-1");
+    reporter.register_instance("service", "instance-2");
+    reporter.register_instance("service", "instance-1");
+    assert_eq!(reporter.instances().len(), 2);
+
+    reporter.keep_alive().await.unwrap();
+    {
+        let pings = service.pings.lock().unwrap();
+        assert_eq!(pings.len(), 2);
+        assert_eq!(pings[0].service, "service");
+        assert_eq!(pings[0].service_instance, "instance-1");
+        assert_eq!(pings[1].service, "service");
+        assert_eq!(pings[1].service_instance, "instance-2");
+    }
+
+    assert_eq!(reporter.unregister_instance("service", "instance-1"), true);
+    assert_eq!(reporter.unregister_instance("service", "instance-1"), false);
+    reporter.keep_alive().await.unwrap();
+    let pings = service.pings.lock().unwrap();
+    assert_eq!(pings.len(), 3);
+    assert_eq!(pings[2].service_instance, "instance-2");
+}
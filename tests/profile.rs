@@ -0,0 +1,133 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::reporter::profile::{ProfileTaskClient, ProfileTaskCommand};
+use rs2sky::skywalking_proto::v3::profile_task_server::{ProfileTask, ProfileTaskServer};
+use rs2sky::skywalking_proto::v3::{
+    Command, Commands, KeyStringValuePair, ProfileTaskCommandQuery, ProfileTaskFinishReport,
+    ThreadSnapshot,
+};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+#[derive(Default, Clone)]
+struct MockProfileService {
+    queries: Arc<Mutex<Vec<ProfileTaskCommandQuery>>>,
+}
+
+fn profile_task_command() -> Command {
+    let args = vec![
+        ("SerialNumber", "serial-1"),
+        ("TaskId", "task-1"),
+        ("EndpointName", "/ping"),
+        ("Duration", "5"),
+        ("MinDurationThreshold", "100"),
+        ("DumpPeriod", "10"),
+        ("MaxSamplingCount", "5"),
+        ("StartTime", "1000"),
+        ("CreateTime", "900"),
+    ];
+    Command {
+        command: "ProfileTaskQuery".to_string(),
+        args: args
+            .into_iter()
+            .map(|(key, value)| KeyStringValuePair {
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl ProfileTask for MockProfileService {
+    async fn get_profile_task_commands(
+        &self,
+        request: Request<ProfileTaskCommandQuery>,
+    ) -> Result<Response<Commands>, Status> {
+        self.queries.lock().unwrap().push(request.into_inner());
+        Ok(Response::new(Commands {
+            commands: vec![profile_task_command()],
+        }))
+    }
+
+    async fn collect_snapshot(
+        &self,
+        _request: Request<Streaming<ThreadSnapshot>>,
+    ) -> Result<Response<Commands>, Status> {
+        Ok(Response::new(Commands::default()))
+    }
+
+    async fn report_task_finish(
+        &self,
+        _request: Request<ProfileTaskFinishReport>,
+    ) -> Result<Response<Commands>, Status> {
+        Ok(Response::new(Commands::default()))
+    }
+}
+
+#[tokio::test]
+async fn fetch_profile_task() {
+    let service = MockProfileService::default();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let server = service.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ProfileTaskServer::new(server))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+
+    let mut client = ProfileTaskClient::connect(address, "service", "instance")
+        .await
+        .unwrap();
+    let tasks = client.get_profile_task_commands().await.unwrap();
+    assert_eq!(
+        tasks,
+        vec![ProfileTaskCommand {
+            serial_number: "serial-1".to_string(),
+            task_id: "task-1".to_string(),
+            endpoint_name: "/ping".to_string(),
+            duration: 5,
+            min_duration_threshold: 100,
+            dump_period: 10,
+            max_sampling_count: 5,
+            start_time: 1000,
+            create_time: 900,
+        }]
+    );
+
+    client.get_profile_task_commands().await.unwrap();
+    let queries = service.queries.lock().unwrap();
+    assert_eq!(queries[0].service, "service");
+    assert_eq!(queries[0].service_instance, "instance");
+    assert_eq!(queries[0].last_command_time, 0);
+    assert_eq!(queries[1].last_command_time, 900);
+}
+
+#[test]
+fn ignore_unknown_command() {
+    let command = Command {
+        command: "Unknown".to_string(),
+        args: Vec::new(),
+    };
+    assert_eq!(ProfileTaskCommand::from_command(&command), None);
+}
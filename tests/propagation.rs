@@ -17,9 +17,15 @@
 #![allow(unused_imports)]
 use rs2sky::common::time::TimeFetcher;
 use rs2sky::context::propagation::context::PropagationContext;
-use rs2sky::context::propagation::decoder::decode_propagation;
-use rs2sky::context::propagation::encoder::encode_propagation;
+use rs2sky::context::propagation::decoder::{
+    decode_extension, decode_propagation, decode_propagation_lenient,
+    decode_propagation_with_options, try_decode_propagation, DecodeOptions,
+};
+use rs2sky::context::propagation::encoder::{
+    encode_extension, encode_propagation, UNKNOWN_DESTINATION,
+};
 use rs2sky::context::trace_context::TracingContext;
+use rs2sky::error::DecodeError;
 use std::sync::Arc;
 
 struct MockTimeFetcher {}
@@ -73,9 +79,301 @@ fn invalid_sample() {
 fn basic_encode() {
     let time_fetcher = MockTimeFetcher {};
     let tc = TracingContext::default_internal(Arc::new(time_fetcher), "mesh", "instance");
-    let res = encode_propagation(&tc, "/api/v1/health", "example.com:8080");
+    let res = encode_propagation(&tc, Some("/api/v1/health"), Some("example.com:8080"));
     let res2 = decode_propagation(&res).unwrap();
     assert_eq!(true, res2.do_sample);
     assert_eq!("/api/v1/health", res2.destination_endpoint);
     assert_eq!("example.com:8080", res2.destination_address)
 }
+
+#[test]
+fn locals_are_not_propagated() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut tc = TracingContext::default_internal(Arc::new(time_fetcher), "mesh", "instance");
+    tc.set_local("cache_key", "user-1234");
+    assert_eq!(tc.get_local("cache_key"), Some("user-1234"));
+    assert_eq!(tc.get_local("unknown"), None);
+
+    let res = encode_propagation(&tc, Some("/api/v1/health"), Some("example.com:8080"));
+    assert_eq!(res.contains(&base64::encode("user-1234")), false);
+    assert_eq!(res.contains(&base64::encode("cache_key")), false);
+}
+
+#[test]
+fn not_sampled_context() {
+    let data = "0-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let prop = decode_propagation(data).unwrap();
+    let time_fetcher = MockTimeFetcher {};
+    let mut tc = TracingContext::from_propagation_context_internal(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        prop,
+    );
+    assert_eq!(tc.is_sampled(), false);
+
+    let mut span1 = tc.create_entry_span("op1").unwrap();
+    span1.add_tag(("hoge", "fuga"));
+    span1.add_log(vec![("hoge", "fuga")]);
+    assert_eq!(span1.is_noop(), true);
+    assert_eq!(span1.span_object().operation_name.is_empty(), true);
+    assert_eq!(span1.tags().is_empty(), true);
+    assert_eq!(span1.logs().is_empty(), true);
+    assert_eq!(span1.span_object().refs.is_empty(), true);
+
+    let span2 = tc.create_exit_span("op2", "example.com:8080").unwrap();
+    assert_eq!(span2.is_noop(), true);
+    assert_eq!(span2.span_object().operation_name.is_empty(), true);
+    assert_eq!(span2.span_object().peer.is_empty(), true);
+    let res = encode_propagation(&tc, Some("/api/v1/health"), Some("example.com:8080"));
+    assert_eq!(decode_propagation(&res).unwrap().do_sample, false);
+
+    tc.finalize_span(span2);
+    tc.finalize_span(span1);
+    assert_eq!(tc.spans.capacity(), 0);
+    assert_eq!(tc.convert_segment_object().spans.is_empty(), true);
+}
+
+#[test]
+fn encode_active_span_as_parent() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut tc = TracingContext::default_internal(Arc::new(time_fetcher), "mesh", "instance");
+    let entry = tc.create_entry_span("op1").unwrap();
+    let exit = tc.create_exit_span("op2", "example.com:8080").unwrap();
+    assert_eq!(
+        decode_propagation(&encode_propagation(&tc, None, None))
+            .unwrap()
+            .parent_span_id,
+        1
+    );
+
+    // The exit span is finalized, so the entry span is the parent again.
+    tc.finalize_span(exit);
+    let res = decode_propagation(&encode_propagation(&tc, None, None)).unwrap();
+    assert_eq!(res.parent_span_id, 0);
+    assert_eq!(
+        res.parent_span_id,
+        tc.make_downstream_context("", "").parent_span_id
+    );
+    tc.finalize_span(entry);
+}
+
+#[test]
+fn encode_unknown_destination() {
+    let time_fetcher = MockTimeFetcher {};
+    let tc = TracingContext::default_internal(Arc::new(time_fetcher), "mesh", "instance");
+    let res = encode_propagation(&tc, Some("/api/v1/health"), None);
+    let res2 = decode_propagation(&res).unwrap();
+    assert_eq!(res2.destination_endpoint, "/api/v1/health");
+    assert_eq!(res2.destination_address, UNKNOWN_DESTINATION);
+
+    let res = encode_propagation(&tc, None, None);
+    let res2 = decode_propagation(&res).unwrap();
+    assert_eq!(res2.destination_endpoint, UNKNOWN_DESTINATION);
+    assert_eq!(res2.destination_address, UNKNOWN_DESTINATION);
+}
+
+#[test]
+fn multi_value_header() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==,1-Mg==-Ng==-4-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let res = decode_propagation(data).unwrap();
+
+    assert_eq!(res.parent_trace_id, "1");
+    assert_eq!(res.parent_trace_segment_id, "5");
+    assert_eq!(res.parent_span_id, 3);
+    assert_eq!(res.destination_address, "example.com:8080");
+}
+
+#[test]
+fn lenient_invalid_utf8() {
+    // The parent service is "f" followed by the invalid byte 0xff.
+    let data = "1-MQ==-NQ==-3-Zv8=-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(decode_propagation(data).is_err(), true);
+
+    let res = decode_propagation_lenient(data).unwrap();
+    assert_eq!(res.parent_trace_id, "1");
+    assert_eq!(res.parent_service, "f\u{FFFD}");
+    assert_eq!(res.parent_service_instance, "instance");
+
+    // The trace ID is still strict.
+    let data = "1-Zv8=-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(decode_propagation_lenient(data).is_err(), true);
+}
+
+#[test]
+fn parent_span_id_out_of_range() {
+    let data = "1-MQ==-NQ==-4294967295-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(
+        decode_propagation(data).err(),
+        Some(DecodeError("span id from parent is out of range."))
+    );
+
+    // A negative span ID can't be in the header, since '-' separates the fields.
+    let data = "1-MQ==-NQ==--1-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(
+        decode_propagation(data).err(),
+        Some(DecodeError(
+            "failed to parse propagation context: it must have 8 properties."
+        ))
+    );
+
+    let data = "1-MQ==-NQ==-2147483648-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(
+        decode_propagation(data).err(),
+        Some(DecodeError("span id from parent is out of range."))
+    );
+
+    let data =
+        "1-MQ==-NQ==-one-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(
+        decode_propagation(data).err(),
+        Some(DecodeError("failed to parse span id from parent."))
+    );
+
+    let data = "1-MQ==-NQ==-2147483647-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(decode_propagation(data).unwrap().parent_span_id, i32::MAX);
+    let data = "1-MQ==-NQ==-0-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    assert_eq!(decode_propagation(data).unwrap().parent_span_id, 0);
+}
+
+#[test]
+fn encode_unsampled_root() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut tc = TracingContext::default_internal(Arc::new(time_fetcher), "mesh", "instance");
+    tc.set_sampled(false);
+    let _span = tc.create_entry_span("op1").unwrap();
+
+    let res = encode_propagation(&tc, Some("/api/v1/health"), Some("example.com:8080"));
+    assert_eq!(res.starts_with("0-"), true);
+    assert_eq!(decode_propagation(&res).unwrap().do_sample, false);
+}
+
+#[test]
+fn try_decode() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let res = try_decode_propagation(Some(data)).unwrap();
+    assert_eq!(res.parent_trace_id, "1");
+
+    assert_eq!(try_decode_propagation(None).is_none(), true);
+    assert_eq!(try_decode_propagation(Some("")).is_none(), true);
+    assert_eq!(try_decode_propagation(Some("1-MQ==")).is_none(), true);
+}
+
+#[test]
+fn decode_with_whitespaces() {
+    let data =
+        " 1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==\r\n";
+    let res = decode_propagation(data).unwrap();
+
+    assert_eq!(res.do_sample, true);
+    assert_eq!(res.parent_trace_id, "1");
+    assert_eq!(res.destination_address, "example.com:8080");
+}
+
+#[test]
+fn decode_percent_encoded() {
+    let data =
+        "1-MQ%3D%3D-NQ%3D%3D-3-bWVzaA%3D%3D-aW5zdGFuY2U%3D-L2FwaS92MS9oZWFsdGg%3D-ZXhhbXBsZS5jb206ODA4MA%3D%3D";
+    assert_eq!(decode_propagation(data).is_err(), true);
+
+    let options = DecodeOptions {
+        percent_decode: true,
+        ..Default::default()
+    };
+    let res = decode_propagation_with_options(data, &options).unwrap();
+    assert_eq!(res.parent_trace_id, "1");
+    assert_eq!(res.parent_trace_segment_id, "5");
+    assert_eq!(res.parent_service, "mesh");
+    assert_eq!(res.destination_endpoint, "/api/v1/health");
+    assert_eq!(res.destination_address, "example.com:8080");
+
+    assert_eq!(
+        decode_propagation_with_options("1-MQ%3", &options).is_err(),
+        true
+    );
+}
+
+#[test]
+fn extension_round_trip() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let mut prop = decode_propagation(data).unwrap();
+    assert_eq!(prop.skip_analysis, false);
+    decode_extension(&mut prop, "0-1").unwrap();
+    assert_eq!(prop.span_limit_reached, false);
+    assert_eq!(prop.skip_analysis, true);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut tc = TracingContext::from_propagation_context_internal(
+        Arc::new(time_fetcher),
+        "service",
+        "instance",
+        prop,
+    );
+    let span = tc.create_entry_span("op1").unwrap();
+    assert_eq!(span.span_object().skip_analysis, true);
+    assert_eq!(encode_extension(&tc), "0-1");
+    tc.finalize_span(span);
+
+    let time_fetcher = MockTimeFetcher {};
+    let tc = TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    assert_eq!(encode_extension(&tc), "0-0");
+}
+
+#[test]
+fn invalid_extension() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let mut prop = decode_propagation(data).unwrap();
+    assert_eq!(decode_extension(&mut prop, "1").is_err(), true);
+    assert_eq!(decode_extension(&mut prop, "0-2").is_err(), true);
+    assert_eq!(decode_extension(&mut prop, "0-1-1").is_err(), true);
+}
+
+#[test]
+fn decode_extra_fields() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==-aG9nZQ==";
+    assert_eq!(decode_propagation(data).is_err(), true);
+
+    let options = DecodeOptions {
+        allow_extra_fields: true,
+        ..Default::default()
+    };
+    let res = decode_propagation_with_options(data, &options).unwrap();
+    assert_eq!(res.parent_trace_id, "1");
+    assert_eq!(res.parent_span_id, 3);
+    assert_eq!(res.destination_address, "example.com:8080");
+
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=";
+    assert_eq!(
+        decode_propagation_with_options(data, &options).is_err(),
+        true
+    );
+}
+
+#[test]
+fn read_through_accessors() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let res = decode_propagation(data).unwrap();
+
+    assert_eq!(res.is_sampled(), true);
+    assert_eq!(res.trace_id(), "1");
+    assert_eq!(res.segment_id(), "5");
+    assert_eq!(res.span_id(), 3);
+    assert_eq!(res.service(), "mesh");
+    assert_eq!(res.service_instance(), "instance");
+    assert_eq!(res.endpoint(), "/api/v1/health");
+    assert_eq!(res.address(), "example.com:8080");
+}
+
+#[test]
+fn decode_empty_fields() {
+    let data = "1-MQ==-NQ==-3--aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let res = decode_propagation(data).unwrap();
+    assert_eq!(res.parent_service, "");
+    assert_eq!(res.parent_service_instance, "instance");
+
+    let data = "1-MQ==-NQ==-3---L2FwaS92MS9oZWFsdGg=-";
+    let res = decode_propagation_lenient(data).unwrap();
+    assert_eq!(res.parent_service, "");
+    assert_eq!(res.parent_service_instance, "");
+    assert_eq!(res.destination_address, "");
+}
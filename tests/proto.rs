@@ -0,0 +1,42 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::trace_context::TracingContext;
+use rs2sky::proto::{SegmentObject, SpanLayer, SpanType};
+use std::sync::Arc;
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+#[test]
+fn reexported_types() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context
+        .record_span_with_duration("op1", std::time::Duration::from_millis(10), SpanType::Entry)
+        .unwrap();
+
+    let segment: SegmentObject = context.convert_segment_object();
+    assert_eq!(segment.spans[0].span_type, SpanType::Entry as i32);
+    assert_eq!(segment.spans[0].span_layer, SpanLayer::Http as i32);
+}
@@ -0,0 +1,687 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use prost::Message;
+use rs2sky::context::trace_context::TracingContext;
+use rs2sky::reporter::grpc::{
+    split_segment, GrpcReporter, InvalidSpanPolicy, INVALID_DURATION_TAG,
+};
+use rs2sky::reporter::reporter_trait::{Reporter, ReporterError};
+use rs2sky::reporter::spool::Spool;
+use rs2sky::skywalking_proto::v3::trace_segment_report_service_server::{
+    TraceSegmentReportService, TraceSegmentReportServiceServer,
+};
+use rs2sky::skywalking_proto::v3::{
+    Commands, KeyStringValuePair, RefType, SegmentCollection, SegmentObject, SpanObject,
+};
+use rs2sky::Error;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+/// A single `collect` call which the collector received.
+struct CollectCall {
+    tenant: Option<String>,
+    segments: Vec<SegmentObject>,
+}
+
+#[derive(Default, Clone)]
+struct MockCollector {
+    calls: Arc<Mutex<Vec<CollectCall>>>,
+}
+
+#[tonic::async_trait]
+impl TraceSegmentReportService for MockCollector {
+    async fn collect(
+        &self,
+        request: Request<Streaming<SegmentObject>>,
+    ) -> Result<Response<Commands>, Status> {
+        let tenant = request
+            .metadata()
+            .get("tenant")
+            .map(|value| value.to_str().unwrap().to_string());
+        let mut stream = request.into_inner();
+        let mut segments = Vec::new();
+        while let Some(segment) = stream.message().await? {
+            segments.push(segment);
+        }
+        self.calls
+            .lock()
+            .unwrap()
+            .push(CollectCall { tenant, segments });
+        Ok(Response::new(Commands::default()))
+    }
+
+    async fn collect_in_sync(
+        &self,
+        _request: Request<SegmentCollection>,
+    ) -> Result<Response<Commands>, Status> {
+        Ok(Response::new(Commands::default()))
+    }
+}
+
+async fn start_collector(collector: MockCollector) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(TraceSegmentReportServiceServer::new(collector))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+    address
+}
+
+fn segment(segment_id: &str) -> SegmentObject {
+    SegmentObject {
+        trace_id: "trace".to_string(),
+        trace_segment_id: segment_id.to_string(),
+        spans: vec![SpanObject {
+            operation_name: "op1".to_string(),
+            start_time: 100,
+            end_time: 100,
+            ..Default::default()
+        }],
+        service: "service".to_string(),
+        service_instance: "instance".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn report_with_tenant() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address.clone())
+        .await
+        .unwrap()
+        .with_tenant("tenant-a".to_string())
+        .unwrap();
+    reporter.report(segment("1")).unwrap();
+    reporter.flush().await.unwrap();
+
+    let mut reporter = GrpcReporter::connect(address).await.unwrap();
+    reporter.report(segment("2")).unwrap();
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].tenant.as_deref(), Some("tenant-a"));
+    assert_eq!(calls[0].segments[0].trace_segment_id, "1");
+    assert_eq!(calls[1].tenant, None);
+    assert_eq!(calls[1].segments[0].trace_segment_id, "2");
+}
+
+#[tokio::test]
+async fn invalid_tenant() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let res = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_tenant("tenant\n".to_string());
+    assert_eq!(
+        matches!(
+            res,
+            Err(Error::Reporter(ReporterError::InvalidTenant(ref tenant))) if tenant == "tenant\n"
+        ),
+        true
+    );
+}
+
+#[tokio::test]
+async fn dedup_segments() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address).await.unwrap().with_dedup(16);
+    reporter.report(segment("1")).unwrap();
+    reporter.flush().await.unwrap();
+    reporter.report(segment("1")).unwrap();
+    reporter.report(segment("2")).unwrap();
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    let received: Vec<&str> = calls
+        .iter()
+        .flat_map(|call| call.segments.iter())
+        .map(|segment| segment.trace_segment_id.as_str())
+        .collect();
+    assert_eq!(received, vec!["1", "2"]);
+}
+
+#[tokio::test]
+async fn report_partial_with_dedup() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address).await.unwrap().with_dedup(16);
+    let mut context = TracingContext::default("service", "instance");
+    let entry = context.create_entry_span("stream").unwrap();
+    let child = context.create_local_span("message1").unwrap();
+    context.finalize_span(child);
+    context.report_partial(&mut reporter).unwrap();
+    let child = context.create_local_span("message2").unwrap();
+    context.finalize_span(child);
+    context.report_partial(&mut reporter).unwrap();
+    context.finalize_span(entry);
+    let segment = context.convert_segment_object();
+    reporter.report(segment.clone()).unwrap();
+    // Retry of the same piece is dropped.
+    reporter.report(segment).unwrap();
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    let received: Vec<&SegmentObject> =
+        calls.iter().flat_map(|call| call.segments.iter()).collect();
+    assert_eq!(received.len(), 3);
+    let operation_names: Vec<&str> = received
+        .iter()
+        .flat_map(|segment| segment.spans.iter())
+        .map(|span| span.operation_name.as_str())
+        .collect();
+    assert_eq!(operation_names, vec!["message1", "message2", "stream"]);
+    for segment in received.iter() {
+        assert_eq!(segment.trace_segment_id, context.trace_segment_id);
+    }
+    // The entry span, the parent of the others, is in the final piece.
+    assert_eq!(
+        received[0].spans[0].parent_span_id,
+        received[2].spans[0].span_id
+    );
+    assert_eq!(
+        received[1].spans[0].parent_span_id,
+        received[2].spans[0].span_id
+    );
+}
+
+fn large_segment(span_count: usize) -> SegmentObject {
+    let mut segment = segment("large");
+    segment.spans = (0..span_count)
+        .map(|i| SpanObject {
+            span_id: i as i32,
+            operation_name: "x".repeat(100),
+            ..Default::default()
+        })
+        .collect();
+    segment
+}
+
+#[test]
+fn split_oversized_segment() {
+    let segment = large_segment(100);
+    let total_size = segment.encoded_len();
+    let segments = split_segment(segment, total_size / 4);
+
+    assert_eq!(segments.len() > 1, true);
+    assert_eq!(segments[0].trace_segment_id, "large");
+    assert_eq!(segments[0].spans[0].refs.is_empty(), true);
+    for split in segments.iter() {
+        assert_eq!(split.trace_id, "trace");
+        assert_eq!(split.is_size_limited, true);
+        assert_eq!(split.encoded_len() <= total_size / 4, true);
+    }
+    // The following pieces are linked to the parent span in the first one.
+    for split in segments.iter().skip(1) {
+        let first = &split.spans[0];
+        assert_eq!(first.refs.len(), 1);
+        assert_eq!(first.refs[0].ref_type, RefType::CrossThread as i32);
+        assert_eq!(first.refs[0].parent_trace_segment_id, "large");
+        assert_eq!(first.refs[0].parent_span_id, first.parent_span_id);
+        assert_eq!(
+            split.spans[1..].iter().all(|span| span.refs.is_empty()),
+            true
+        );
+    }
+    let span_count: usize = segments.iter().map(|split| split.spans.len()).sum();
+    assert_eq!(span_count, 100);
+}
+
+#[tokio::test]
+async fn report_oversized_segment() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_max_message_size(2048);
+    reporter.report(large_segment(100)).unwrap();
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].segments.len() > 1, true);
+    let span_count: usize = calls[0]
+        .segments
+        .iter()
+        .map(|split| split.spans.len())
+        .sum();
+    assert_eq!(span_count, 100);
+}
+
+#[tokio::test]
+async fn high_watermark() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let fired = Arc::new(Mutex::new(Vec::<usize>::new()));
+    let fired_in_callback = fired.clone();
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_capacity(4)
+        .on_high_watermark(0.5, move |occupancy| {
+            fired_in_callback.lock().unwrap().push(occupancy)
+        });
+
+    reporter.report(segment("1")).unwrap();
+    assert_eq!(fired.lock().unwrap().is_empty(), true);
+    reporter.report(segment("2")).unwrap();
+    reporter.report(segment("3")).unwrap();
+    reporter.report(segment("4")).unwrap();
+    assert_eq!(reporter.report(segment("5")).is_err(), true);
+    assert_eq!(*fired.lock().unwrap(), vec![2]);
+
+    // It is armed again once the queue is drained.
+    reporter.flush().await.unwrap();
+    reporter.report(segment("6")).unwrap();
+    reporter.report(segment("7")).unwrap();
+    assert_eq!(*fired.lock().unwrap(), vec![2, 2]);
+}
+
+#[tokio::test]
+async fn high_watermark_on_spool_recovery() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let path = std::env::temp_dir().join(format!("rs2sky-watermark-{}.spool", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    {
+        let mut spool = Spool::open(&path).unwrap();
+        for id in ["1", "2", "3"].iter() {
+            spool.append(&segment(id)).unwrap();
+        }
+    }
+
+    let fired = Arc::new(Mutex::new(Vec::<usize>::new()));
+    let fired_in_callback = fired.clone();
+    let reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_capacity(2)
+        .on_high_watermark(0.5, move |occupancy| {
+            fired_in_callback.lock().unwrap().push(occupancy)
+        })
+        .with_spool(Spool::open(&path).unwrap())
+        .unwrap();
+
+    assert_eq!(reporter.queued(), 2);
+    assert_eq!(*fired.lock().unwrap(), vec![2]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn recover_more_than_capacity() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let path = std::env::temp_dir().join(format!("rs2sky-overflow-{}.spool", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    {
+        let mut spool = Spool::open(&path).unwrap();
+        for id in ["1", "2", "3", "4", "5"].iter() {
+            spool.append(&segment(id)).unwrap();
+        }
+    }
+
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_capacity(2)
+        .with_spool(Spool::open(&path).unwrap())
+        .unwrap();
+    assert_eq!(reporter.queued(), 2);
+
+    reporter.flush().await.unwrap();
+    // Segments which are not delivered yet stay in the spool.
+    assert_eq!(Spool::open(&path).unwrap().pending().unwrap().len(), 3);
+    assert_eq!(reporter.queued(), 2);
+    reporter.flush().await.unwrap();
+    assert_eq!(reporter.queued(), 1);
+    reporter.flush().await.unwrap();
+    assert_eq!(reporter.queued(), 0);
+    assert_eq!(
+        Spool::open(&path).unwrap().pending().unwrap().is_empty(),
+        true
+    );
+
+    let calls = collector.calls.lock().unwrap();
+    let received: Vec<&str> = calls
+        .iter()
+        .flat_map(|call| call.segments.iter())
+        .map(|segment| segment.trace_segment_id.as_str())
+        .collect();
+    assert_eq!(received, vec!["1", "2", "3", "4", "5"]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn report_all_in_one_call() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address).await.unwrap();
+    reporter
+        .report_all(vec![segment("1"), segment("2"), segment("3")])
+        .await
+        .unwrap();
+    assert_eq!(reporter.queued(), 0);
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls.len(), 1);
+    let received: Vec<&str> = calls[0]
+        .segments
+        .iter()
+        .map(|segment| segment.trace_segment_id.as_str())
+        .collect();
+    assert_eq!(received, vec!["1", "2", "3"]);
+}
+
+#[tokio::test]
+async fn report_all_with_dedup_and_rate_limit() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_dedup(16)
+        .with_rate_limit(3);
+    reporter
+        .report_all(vec![
+            segment("1"),
+            segment("1"),
+            segment("2"),
+            segment("3"),
+            segment("4"),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(reporter.rate_limited_count(), 2);
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls.len(), 1);
+    let received: Vec<&str> = calls[0]
+        .segments
+        .iter()
+        .map(|segment| segment.trace_segment_id.as_str())
+        .collect();
+    assert_eq!(received, vec!["1", "2"]);
+}
+
+/// Segment which has a closed span and an unclosed span.
+fn segment_with_unclosed_span(segment_id: &str) -> SegmentObject {
+    let mut segment = segment(segment_id);
+    segment.spans.push(SpanObject {
+        span_id: 1,
+        operation_name: "op2".to_string(),
+        start_time: 100,
+        end_time: 0,
+        ..Default::default()
+    });
+    segment
+}
+
+#[tokio::test]
+async fn invalid_span_policy() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address.clone())
+        .await
+        .unwrap()
+        .with_invalid_span_policy(InvalidSpanPolicy::Drop);
+    reporter.report(segment_with_unclosed_span("1")).unwrap();
+    reporter.flush().await.unwrap();
+    assert_eq!(reporter.invalid_span_count(), 1);
+
+    let mut reporter = GrpcReporter::connect(address.clone())
+        .await
+        .unwrap()
+        .with_invalid_span_policy(InvalidSpanPolicy::Flag);
+    reporter.report(segment_with_unclosed_span("2")).unwrap();
+    reporter.flush().await.unwrap();
+    assert_eq!(reporter.invalid_span_count(), 1);
+
+    let mut reporter = GrpcReporter::connect(address).await.unwrap();
+    reporter.report(segment_with_unclosed_span("3")).unwrap();
+    reporter.flush().await.unwrap();
+    assert_eq!(reporter.invalid_span_count(), 1);
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls.len(), 3);
+
+    let dropped = &calls[0].segments[0];
+    assert_eq!(dropped.spans.len(), 1);
+    assert_eq!(dropped.spans[0].operation_name, "op1");
+
+    let flagged = &calls[1].segments[0];
+    assert_eq!(flagged.spans.len(), 2);
+    assert_eq!(flagged.spans[0].is_error, false);
+    assert_eq!(flagged.spans[1].is_error, true);
+    assert_eq!(flagged.spans[1].tags[0].key, INVALID_DURATION_TAG);
+
+    let passed = &calls[2].segments[0];
+    assert_eq!(passed.spans.len(), 2);
+    assert_eq!(passed.spans[1].is_error, false);
+    assert_eq!(passed.spans[1].tags.is_empty(), true);
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_rate_limit(3);
+    for i in 0..10 {
+        reporter.report(segment(&i.to_string())).unwrap();
+    }
+    assert_eq!(reporter.rate_limited_count() >= 6, true);
+    let queued = reporter.queued();
+    assert_eq!(queued + reporter.rate_limited_count(), 10);
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls[0].segments.len(), queued);
+    assert_eq!(calls[0].segments[0].trace_segment_id, "0");
+}
+
+#[tokio::test]
+async fn transform_segments() {
+    let collector = MockCollector::default();
+    let address = start_collector(collector.clone()).await;
+
+    let mut reporter = GrpcReporter::connect(address)
+        .await
+        .unwrap()
+        .with_transform(|mut segment| {
+            if segment
+                .spans
+                .iter()
+                .any(|span| span.operation_name == "/healthz")
+            {
+                return None;
+            }
+            for span in segment.spans.iter_mut() {
+                for tag in span.tags.iter_mut().filter(|tag| tag.key == "user.email") {
+                    tag.value = "<redacted>".to_string();
+                }
+            }
+            Some(segment)
+        });
+
+    let mut redacted = segment("1");
+    redacted.spans[0].tags.push(KeyStringValuePair {
+        key: "user.email".to_string(),
+        value: "alice@example.com".to_string(),
+    });
+    reporter.report(redacted).unwrap();
+
+    let mut dropped = segment("2");
+    dropped.spans[0].operation_name = "/healthz".to_string();
+    reporter.report(dropped).unwrap();
+    reporter.flush().await.unwrap();
+
+    let calls = collector.calls.lock().unwrap();
+    assert_eq!(calls[0].segments.len(), 1);
+    assert_eq!(calls[0].segments[0].trace_segment_id, "1");
+    assert_eq!(calls[0].segments[0].spans[0].tags[0].value, "<redacted>");
+}
+
+/// `AsyncReporter::report` conflicts with `Reporter::report` of `GrpcReporter`,
+/// so they are tested in a separate scope.
+mod async_reporter {
+    use super::{segment, start_collector, MockCollector};
+    use rs2sky::reporter::grpc::GrpcReporter;
+    use rs2sky::reporter::reporter_trait::{AsyncReporter, ReporterError};
+    use rs2sky::skywalking_proto::v3::trace_segment_report_service_server::{
+        TraceSegmentReportService, TraceSegmentReportServiceServer,
+    };
+    use rs2sky::skywalking_proto::v3::{Commands, SegmentCollection, SegmentObject};
+    use rs2sky::Error;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status, Streaming};
+
+    #[derive(Default, Clone)]
+    struct RecordingReporter {
+        segments: Arc<Mutex<Vec<SegmentObject>>>,
+    }
+
+    #[tonic::async_trait]
+    impl AsyncReporter for RecordingReporter {
+        async fn report(&mut self, segment: SegmentObject) -> rs2sky::Result<()> {
+            tokio::task::yield_now().await;
+            self.segments.lock().unwrap().push(segment);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_async_reporter() {
+        let recording = RecordingReporter::default();
+        let mut reporter: Box<dyn AsyncReporter + Send> = Box::new(recording.clone());
+        reporter.report(segment("1")).await.unwrap();
+        reporter.report(segment("2")).await.unwrap();
+
+        let segments = recording.segments.lock().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].trace_segment_id, "2");
+    }
+
+    /// Collector which is unavailable.
+    struct FailingCollector;
+
+    #[tonic::async_trait]
+    impl TraceSegmentReportService for FailingCollector {
+        async fn collect(
+            &self,
+            _request: Request<Streaming<SegmentObject>>,
+        ) -> Result<Response<Commands>, Status> {
+            Err(Status::unavailable("collector is down"))
+        }
+
+        async fn collect_in_sync(
+            &self,
+            _request: Request<SegmentCollection>,
+        ) -> Result<Response<Commands>, Status> {
+            Err(Status::unavailable("collector is down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn backend_error_of_async_reporter() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(TraceSegmentReportServiceServer::new(FailingCollector))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+
+        let mut reporter: Box<dyn AsyncReporter + Send> =
+            Box::new(GrpcReporter::connect(address).await.unwrap());
+        let err = reporter.report(segment("1")).await.unwrap_err();
+        match err {
+            Error::Reporter(ReporterError::Backend(status)) => {
+                assert_eq!(status.code(), tonic::Code::Unavailable);
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn grpc_reporter_as_async_reporter() {
+        let collector = MockCollector::default();
+        let address = start_collector(collector.clone()).await;
+
+        let mut reporter: Box<dyn AsyncReporter + Send> =
+            Box::new(GrpcReporter::connect(address).await.unwrap());
+        reporter.report(segment("1")).await.unwrap();
+
+        let calls = collector.calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].segments[0].trace_segment_id, "1");
+    }
+
+    #[tokio::test]
+    async fn flush_every() {
+        let collector = MockCollector::default();
+        let address = start_collector(collector.clone()).await;
+
+        let mut reporter = GrpcReporter::connect(address).await.unwrap().flush_every(3);
+        reporter.report(segment("1")).await.unwrap();
+        reporter.report(segment("2")).await.unwrap();
+        assert_eq!(collector.calls.lock().unwrap().len(), 0);
+        assert_eq!(reporter.queued(), 2);
+
+        reporter.report(segment("3")).await.unwrap();
+        assert_eq!(reporter.queued(), 0);
+        {
+            let calls = collector.calls.lock().unwrap();
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].segments.len(), 3);
+        }
+
+        reporter.report(segment("4")).await.unwrap();
+        assert_eq!(collector.calls.lock().unwrap().len(), 1);
+    }
+}
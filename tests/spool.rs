@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::reporter::spool::Spool;
+use rs2sky::skywalking_proto::v3::{SegmentObject, SpanObject};
+use std::path::PathBuf;
+
+fn spool_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rs2sky-{}-{}.spool", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn segment(segment_id: &str) -> SegmentObject {
+    SegmentObject {
+        trace_id: "trace".to_string(),
+        trace_segment_id: segment_id.to_string(),
+        spans: vec![SpanObject {
+            operation_name: "op1".to_string(),
+            ..Default::default()
+        }],
+        service: "service".to_string(),
+        service_instance: "instance".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn recover_pending_segments() {
+    let path = spool_path("recover");
+    {
+        let mut spool = Spool::open(&path).unwrap();
+        spool.append(&segment("1")).unwrap();
+        spool.append(&segment("2")).unwrap();
+    }
+
+    // Simulate restart of the process.
+    let spool = Spool::open(&path).unwrap();
+    let pending = spool.pending().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0], segment("1"));
+    assert_eq!(pending[1], segment("2"));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn acknowledged_segments_are_not_recovered() {
+    let path = spool_path("acknowledge");
+    {
+        let mut spool = Spool::open(&path).unwrap();
+        spool.append(&segment("1")).unwrap();
+        spool.acknowledge().unwrap();
+        spool.append(&segment("2")).unwrap();
+    }
+
+    let spool = Spool::open(&path).unwrap();
+    let pending = spool.pending().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].trace_segment_id, "2");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn skip_truncated_tail() {
+    let path = spool_path("truncated");
+    {
+        let mut spool = Spool::open(&path).unwrap();
+        spool.append(&segment("1")).unwrap();
+        spool.append(&segment("2")).unwrap();
+    }
+
+    // Simulate the crash while the last segment was being appended.
+    let len = std::fs::metadata(&path).unwrap().len();
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - 3).unwrap();
+
+    let spool = Spool::open(&path).unwrap();
+    let pending = spool.pending().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].trace_segment_id, "1");
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,102 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use rs2sky::reporter::reporter_trait::{Reporter, ReporterError};
+use rs2sky::reporter::tee::TeeReporter;
+use rs2sky::skywalking_proto::v3::SegmentObject;
+use rs2sky::Error;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default, Clone)]
+struct MockReporter {
+    segments: Arc<Mutex<Vec<SegmentObject>>>,
+    closed: bool,
+}
+
+impl Reporter for MockReporter {
+    fn report(&mut self, segment: SegmentObject) -> rs2sky::Result<()> {
+        if self.closed {
+            return Err(ReporterError::Closed.into());
+        }
+        self.segments.lock().unwrap().push(segment);
+        Ok(())
+    }
+}
+
+fn segment(segment_id: &str) -> SegmentObject {
+    SegmentObject {
+        trace_id: "trace".to_string(),
+        trace_segment_id: segment_id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn forward_to_all_reporters() {
+    let first = MockReporter::default();
+    let second = MockReporter::default();
+    let mut reporter = TeeReporter::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+    reporter.report(segment("1")).unwrap();
+    reporter.report(segment("2")).unwrap();
+
+    for mock in [first, second].iter() {
+        let segments = mock.segments.lock().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].trace_segment_id, "1");
+        assert_eq!(segments[1].trace_segment_id, "2");
+    }
+}
+
+#[test]
+fn forward_even_if_one_fails() {
+    let closed = MockReporter {
+        closed: true,
+        ..Default::default()
+    };
+    let alive = MockReporter::default();
+    let mut reporter = TeeReporter::new(vec![Box::new(closed), Box::new(alive.clone())]);
+
+    assert_eq!(reporter.report(segment("1")).is_err(), true);
+    assert_eq!(alive.segments.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn aggregate_errors() {
+    let closed = MockReporter {
+        closed: true,
+        ..Default::default()
+    };
+    let alive = MockReporter::default();
+    let mut reporter = TeeReporter::new(vec![
+        Box::new(closed.clone()),
+        Box::new(alive.clone()),
+        Box::new(closed),
+    ]);
+
+    match reporter.report(segment("1")) {
+        Err(Error::Reporters(errors)) => {
+            assert_eq!(errors.len(), 2);
+            for error in errors.iter() {
+                assert_eq!(
+                    matches!(error, Error::Reporter(ReporterError::Closed)),
+                    true
+                );
+            }
+        }
+        _ => panic!("errors of all the failed reporters must be returned."),
+    }
+    assert_eq!(alive.segments.lock().unwrap().len(), 1);
+}
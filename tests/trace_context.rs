@@ -27,11 +27,22 @@ use rs2sky::common::time::TimeFetcher;
 use rs2sky::context::propagation::context::PropagationContext;
 use rs2sky::context::propagation::decoder::decode_propagation;
 use rs2sky::context::propagation::encoder::encode_propagation;
-use rs2sky::context::trace_context::TracingContext;
+use rs2sky::context::system_time::UnixTimeStampFetcher;
+use rs2sky::context::trace_context::{
+    display_trace_id, SegmentValidationError, TracingContext, CACHE_KEY_TAG, CACHE_OP_TAG,
+    CACHE_TYPE_TAG, CONTEXT_ALREADY_REPORTED, DB_STATEMENT_TAG, ENTRY_SPAN_NOT_EXIST,
+    REFS_TRUNCATED_TAG, RPC_STATUS_CODE_TAG, SDK_LANGUAGE_TAG, SDK_VERSION_TAG, SELF_TIME_TAG,
+    SOURCE_LOCATION_TAG,
+};
+use rs2sky::reporter::reporter_trait::{MeterReporter, Reporter};
 use skywalking_proto::v3::{
     KeyStringValuePair, Log, RefType, SegmentObject, SegmentReference, SpanLayer, SpanObject,
     SpanType,
 };
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 use std::{cell::Ref, sync::Arc};
 
 /// Serialize from A should equal Serialize from B
@@ -103,8 +114,8 @@ fn create_span() {
         span1.add_tag(tags[0].clone());
 
         let span1_expected = SpanObject {
-            span_id: 1,
-            parent_span_id: 0,
+            span_id: 0,
+            parent_span_id: -1,
             start_time: 100,
             end_time: 100,
             refs: Vec::<SegmentReference>::new(),
@@ -130,8 +141,8 @@ fn create_span() {
     {
         let mut span3 = context.create_exit_span("op3", "example.com/test").unwrap();
         let span3_expected = SpanObject {
-            span_id: 2,
-            parent_span_id: 1,
+            span_id: 1,
+            parent_span_id: 0,
             start_time: 100,
             end_time: 100,
             refs: Vec::<SegmentReference>::new(),
@@ -191,7 +202,7 @@ fn crossprocess_test() {
     let mut span2 = context1.create_exit_span("op2", "remote_peer").unwrap();
     context1.finalize_span_for_test(&mut span2);
 
-    let enc_prop = encode_propagation(&context1, "endpoint", "address");
+    let enc_prop = encode_propagation(&context1, Some("endpoint"), Some("address"));
     let dec_prop = decode_propagation(&enc_prop).unwrap();
 
     let time_fetcher2 = MockTimeFetcher {};
@@ -205,15 +216,15 @@ fn crossprocess_test() {
     let mut span3 = context2.create_entry_span("op2").unwrap();
     context2.finalize_span_for_test(&mut span3);
 
-    assert_eq!(span3.span_object().span_id, 1);
-    assert_eq!(span3.span_object().parent_span_id, 0);
+    assert_eq!(span3.span_object().span_id, 0);
+    assert_eq!(span3.span_object().parent_span_id, -1);
     assert_eq!(span3.span_object().refs.len(), 1);
 
     let expected_ref = SegmentReference {
         ref_type: RefType::CrossProcess as i32,
         trace_id: context2.trace_id,
         parent_trace_segment_id: context1.trace_segment_id,
-        parent_span_id: context1.next_span_id,
+        parent_span_id: span2.span_object().span_id,
         parent_service: context1.service,
         parent_service_instance: context1.service_instance,
         parent_endpoint: "endpoint".to_string(),
@@ -222,3 +233,1675 @@ fn crossprocess_test() {
 
     check_serialize_equivalent(&expected_ref, &span3.span_object().refs[0]);
 }
+
+#[test]
+fn unset_span_fields_take_default() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span = context.create_entry_span("op1").unwrap();
+
+    let expected = SpanObject {
+        span_id: 0,
+        parent_span_id: -1,
+        start_time: 100,
+        operation_name: "op1".to_string(),
+        span_type: SpanType::Entry as i32,
+        span_layer: SpanLayer::Http as i32,
+        component_id: 11000,
+        ..Default::default()
+    };
+    check_serialize_equivalent(span.span_object(), &expected);
+    assert_eq!(span.span_object().end_time, 0);
+    assert_eq!(span.span_object().is_error, false);
+    assert_eq!(span.span_object().refs.is_empty(), true);
+}
+
+#[test]
+fn read_tags_and_logs() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    span.add_tag(("http.method", "GET"));
+    span.add_tag(("http.status_code", "200"));
+    span.add_log(vec![("event", "error"), ("message", "timeout")]);
+    context.finalize_span_for_test(&mut span);
+
+    let tags = span.tags();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[0].key, "http.method");
+    assert_eq!(tags[0].value, "GET");
+    assert_eq!(tags[1].key, "http.status_code");
+    assert_eq!(tags[1].value, "200");
+
+    let logs = span.logs();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].time, 100);
+    assert_eq!(logs[0].data.len(), 2);
+    assert_eq!(logs[0].data[1].key, "message");
+    assert_eq!(logs[0].data[1].value, "timeout");
+}
+
+#[test]
+fn context_has_error() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span1 = context.create_entry_span("op1").unwrap();
+    let mut span2 = context.create_exit_span("op2", "remote_peer").unwrap();
+    span2.set_error(true);
+    context.finalize_span(span2);
+    context.finalize_span(span1);
+    assert_eq!(context.has_error(), true);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span1 = context.create_entry_span("op1").unwrap();
+    let span2 = context.create_exit_span("op2", "remote_peer").unwrap();
+    context.finalize_span(span2);
+    context.finalize_span(span1);
+    assert_eq!(context.has_error(), false);
+}
+
+#[test]
+fn non_entry_span_as_first_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+
+    let local_err = context.create_local_span("op1").err().unwrap();
+    let exit_err = context
+        .create_exit_span("op2", "remote_peer")
+        .err()
+        .unwrap();
+    assert_eq!(local_err.to_string(), ENTRY_SPAN_NOT_EXIST);
+    assert_eq!(exit_err.to_string(), ENTRY_SPAN_NOT_EXIST);
+}
+
+#[test]
+fn create_local_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _span1 = context.create_entry_span("op1").unwrap();
+    let span2 = context.create_local_span("op2").unwrap();
+    assert_eq!(span2.span_object().span_type, SpanType::Local as i32);
+    assert_eq!(span2.span_object().peer, "");
+}
+
+/// Wall clock goes backward on every call, while monotonic clock goes forward.
+struct BackwardTimeFetcher {
+    wall: AtomicI64,
+    monotonic: AtomicI64,
+}
+
+impl TimeFetcher for BackwardTimeFetcher {
+    fn get(&self) -> i64 {
+        self.wall.fetch_sub(600, Ordering::SeqCst)
+    }
+
+    fn get_monotonic(&self) -> i64 {
+        self.monotonic.fetch_add(5, Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn duration_with_backward_clock() {
+    let time_fetcher = BackwardTimeFetcher {
+        wall: AtomicI64::new(1000),
+        monotonic: AtomicI64::new(10),
+    };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    context.finalize_span_for_test(&mut span);
+
+    assert_eq!(span.span_object().start_time, 1000);
+    assert_eq!(span.span_object().end_time, 1005);
+}
+
+#[test]
+fn record_span_with_duration() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context
+        .record_span_with_duration(
+            "op1",
+            Duration::from_millis(250),
+            rs2sky::skywalking_proto::v3::SpanType::Entry,
+        )
+        .unwrap();
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 1);
+    assert_eq!(segment.spans[0].end_time, 100);
+    assert_eq!(segment.spans[0].end_time - segment.spans[0].start_time, 250);
+}
+
+#[test]
+fn rename_operation() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("unknown").unwrap();
+    span.set_operation_name("/users/{id}".to_string());
+    context.finalize_span(span);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans[0].operation_name, "/users/{id}");
+}
+
+#[test]
+fn override_segment_service() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "relay", "relay_instance");
+    let span = context.create_entry_span("op1").unwrap();
+    context.finalize_span(span);
+
+    let segment1 = context.convert_segment_object_with_service("service1", "instance1");
+    let segment2 = context.convert_segment_object_with_service("service2", "instance2");
+    assert_eq!(segment1.service, "service1");
+    assert_eq!(segment1.service_instance, "instance1");
+    assert_eq!(segment2.service, "service2");
+    assert_eq!(segment2.service_instance, "instance2");
+    assert_eq!(segment1.spans.len(), 1);
+    assert_eq!(segment2.spans.len(), 1);
+
+    let segment3 = context.convert_segment_object();
+    assert_eq!(segment3.service, "relay");
+    assert_eq!(segment3.service_instance, "relay_instance");
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "service name must not be empty.")]
+fn empty_service_name() {
+    let time_fetcher = MockTimeFetcher {};
+    TracingContext::default_internal(Arc::new(time_fetcher), " ", "instance");
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "instance name must not be empty.")]
+fn empty_instance_name() {
+    let time_fetcher = MockTimeFetcher {};
+    TracingContext::default_internal(Arc::new(time_fetcher), "service", "");
+}
+
+#[test]
+fn finalize_out_of_order() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span1 = context.create_entry_span("op1").unwrap();
+    let span2 = context.create_exit_span("op2", "remote_peer").unwrap();
+    let span3 = context.create_exit_span("op3", "remote_peer").unwrap();
+    context.finalize_span(span3);
+    context.finalize_span(span1);
+    context.finalize_span(span2);
+
+    let segment = context.convert_segment_object();
+    let ids: Vec<(i32, i32)> = segment
+        .spans
+        .iter()
+        .map(|span| (span.span_id, span.parent_span_id))
+        .collect();
+    assert_eq!(ids, vec![(0, -1), (1, 0), (2, 0)]);
+    assert_eq!(segment.spans[0].operation_name, "op1");
+}
+
+#[test]
+fn root_or_continued() {
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    assert_eq!(context.is_root(), true);
+    assert_eq!(context.parent_endpoint(), None);
+    assert_eq!(context.parent_address(), None);
+
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let prop = decode_propagation(data).unwrap();
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::from_propagation_context_internal(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        prop,
+    );
+    assert_eq!(context.is_root(), false);
+    assert_eq!(context.parent_endpoint(), Some("/api/v1/health"));
+    assert_eq!(context.parent_address(), Some("example.com:8080"));
+}
+
+#[test]
+fn references_distinguished_by_segment() {
+    let mut parents = Vec::new();
+    let mut children = Vec::new();
+    for _ in 0..2 {
+        let time_fetcher = MockTimeFetcher {};
+        let mut parent =
+            TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+        let span = parent.create_entry_span("op1").unwrap();
+        parent.finalize_span(span);
+        let prop = encode_propagation(&parent, Some("endpoint"), Some("address"));
+
+        let time_fetcher = MockTimeFetcher {};
+        let mut child = TracingContext::from_propagation_context_internal(
+            Arc::new(time_fetcher),
+            "service2",
+            "instance2",
+            decode_propagation(&prop).unwrap(),
+        );
+        let span = child.create_entry_span("op2").unwrap();
+        child.finalize_span(span);
+
+        parents.push(parent);
+        children.push(child.convert_segment_object());
+    }
+
+    let ref1 = &children[0].spans[0].refs[0];
+    let ref2 = &children[1].spans[0].refs[0];
+    assert_eq!(ref1.parent_span_id, ref2.parent_span_id);
+    assert_eq!(ref1.parent_trace_segment_id, parents[0].trace_segment_id);
+    assert_eq!(ref2.parent_trace_segment_id, parents[1].trace_segment_id);
+    assert_ne!(ref1.parent_trace_segment_id, ref2.parent_trace_segment_id);
+}
+
+#[test]
+fn context_with_trace_id() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::with_trace_id(
+        Arc::new(time_fetcher),
+        "service",
+        "instance",
+        "req-1234.abcd_ef".to_string(),
+    )
+    .unwrap();
+    let span = context.create_entry_span("op1").unwrap();
+    context.finalize_span(span);
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.trace_id, "req-1234.abcd_ef");
+
+    for invalid in ["", "req 1234", "req/1234"].iter() {
+        let time_fetcher = MockTimeFetcher {};
+        let context = TracingContext::with_trace_id(
+            Arc::new(time_fetcher),
+            "service",
+            "instance",
+            invalid.to_string(),
+        );
+        assert_eq!(context.is_err(), true);
+    }
+}
+
+#[test]
+fn add_tags_and_logs_from_map() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+
+    let mut tags = HashMap::new();
+    tags.insert("http.method".to_string(), "GET".to_string());
+    tags.insert("http.status_code".to_string(), "200".to_string());
+    tags.insert("http.url".to_string(), "/ping".to_string());
+    span.add_tags(tags);
+    assert_eq!(span.tags().len(), 3);
+    for (key, value) in [
+        ("http.method", "GET"),
+        ("http.status_code", "200"),
+        ("http.url", "/ping"),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            span.tags()
+                .iter()
+                .any(|tag| tag.key == *key && tag.value == *value),
+            true
+        );
+    }
+
+    span.add_tags(vec![
+        ("http.status_code".to_string(), "500".to_string()),
+        ("error".to_string(), "false".to_string()),
+        ("error".to_string(), "true".to_string()),
+    ]);
+    assert_eq!(span.tags().len(), 4);
+    let value_of = |key: &str| {
+        span.tags()
+            .iter()
+            .find(|tag| tag.key == key)
+            .map(|tag| tag.value.clone())
+    };
+    assert_eq!(value_of("http.status_code"), Some("500".to_string()));
+    assert_eq!(value_of("error"), Some("true".to_string()));
+
+    span.add_logs(vec![
+        ("event".to_string(), "error".to_string()),
+        ("event".to_string(), "exception".to_string()),
+    ]);
+    assert_eq!(span.logs().len(), 1);
+    assert_eq!(span.logs()[0].data.len(), 1);
+    assert_eq!(span.logs()[0].data[0].value, "exception");
+}
+
+#[test]
+fn make_downstream_context() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span1 = context.create_entry_span("op1").unwrap();
+    let span_id = span1.span_object().span_id;
+    assert_eq!(context.active_span_id(), Some(span_id));
+
+    let next_span_id = context.next_span_id;
+    let prop = context.make_downstream_context("/pong", "consumer:8082");
+    assert_eq!(prop.do_sample, true);
+    assert_eq!(prop.parent_trace_id, context.trace_id);
+    assert_eq!(prop.parent_trace_segment_id, context.trace_segment_id);
+    assert_eq!(prop.parent_span_id, span_id);
+    assert_eq!(prop.parent_service, "service");
+    assert_eq!(prop.parent_service_instance, "instance");
+    assert_eq!(prop.destination_endpoint, "op1");
+    assert_eq!(prop.destination_address, "consumer:8082");
+    assert_eq!(context.next_span_id, next_span_id);
+    assert_eq!(context.spans.is_empty(), true);
+
+    context.finalize_span(span1);
+    assert_eq!(context.active_span_id(), None);
+}
+
+#[test]
+fn entry_local_exit_chain() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span1 = context.create_entry_span("op1").unwrap();
+    let span2 = context.create_local_span("op2").unwrap();
+    let span3 = context.create_exit_span("op3", "remote_peer").unwrap();
+    context.finalize_span(span3);
+    context.finalize_span(span2);
+    context.finalize_span(span1);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 3);
+
+    let entry = &segment.spans[0];
+    assert_eq!(entry.span_type, SpanType::Entry as i32);
+    assert_eq!(entry.span_layer, SpanLayer::Http as i32);
+    assert_eq!(entry.end_time, 100);
+
+    let local = &segment.spans[1];
+    assert_eq!(local.span_type, SpanType::Local as i32);
+    assert_eq!(local.span_layer, SpanLayer::Unknown as i32);
+    assert_eq!(local.parent_span_id, entry.span_id);
+    assert_eq!(local.end_time, 100);
+
+    let exit = &segment.spans[2];
+    assert_eq!(exit.span_type, SpanType::Exit as i32);
+    assert_eq!(exit.span_layer, SpanLayer::Http as i32);
+    assert_eq!(exit.parent_span_id, local.span_id);
+    assert_eq!(exit.peer, "remote_peer");
+    assert_eq!(exit.end_time, 100);
+}
+
+#[test]
+fn finalize_span_at() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span = context.create_entry_span("op1").unwrap();
+    context.finalize_span_at(span, 999);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans[0].start_time, 100);
+    assert_eq!(segment.spans[0].end_time, 999);
+}
+
+#[test]
+fn count_spans() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _span1 = context.create_entry_span("op1").unwrap();
+    let span2 = context.create_exit_span("op2", "remote_peer").unwrap();
+    context.finalize_span(span2);
+    let span3 = context.create_exit_span("op3", "remote_peer").unwrap();
+    context.finalize_span(span3);
+
+    assert_eq!(context.span_count(), 2);
+    assert_eq!(context.active_span_count(), 1);
+}
+
+#[test]
+fn entry_from_sw8() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let time_fetcher = MockTimeFetcher {};
+    let (context, span) = TracingContext::entry_from_sw8(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        data,
+        "op1",
+    )
+    .unwrap();
+
+    assert_eq!(context.is_root(), false);
+    assert_eq!(context.trace_id, "1");
+    assert_eq!(span.span_object().operation_name, "op1");
+    assert_eq!(span.span_object().span_type, SpanType::Entry as i32);
+    assert_eq!(span.span_object().refs.len(), 1);
+
+    let reference = &span.span_object().refs[0];
+    assert_eq!(reference.ref_type, RefType::CrossProcess as i32);
+    assert_eq!(reference.parent_trace_segment_id, "5");
+    assert_eq!(reference.parent_span_id, 3);
+    assert_eq!(reference.parent_service, "mesh");
+
+    let time_fetcher = MockTimeFetcher {};
+    let res = TracingContext::entry_from_sw8(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        "invalid",
+        "op1",
+    );
+    assert_eq!(res.is_err(), true);
+}
+
+/// Clock which only moves when the test steps it.
+struct StepTimeFetcher {
+    now: Arc<AtomicI64>,
+}
+
+impl TimeFetcher for StepTimeFetcher {
+    fn get(&self) -> i64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn sweep_expired_spans() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    span.set_deadline(Duration::from_millis(50));
+    let span_id = context.detach_span(span);
+
+    now.store(120, Ordering::SeqCst);
+    assert_eq!(context.sweep_expired_spans(), 0);
+    assert_eq!(context.span_count(), 0);
+    assert_eq!(context.active_span_count(), 1);
+
+    now.store(150, Ordering::SeqCst);
+    assert_eq!(context.sweep_expired_spans(), 1);
+    assert_eq!(context.take_detached_span(span_id).is_none(), true);
+    assert_eq!(context.span_count(), 1);
+    assert_eq!(context.active_span_count(), 0);
+
+    let span = context.spans[0].span_object();
+    assert_eq!(span.is_error, true);
+    assert_eq!(span.end_time, 150);
+    assert_eq!(
+        span.tags
+            .iter()
+            .any(|tag| tag.key == "timeout" && tag.value == "true"),
+        true
+    );
+}
+
+#[test]
+fn take_detached_span() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    span.set_deadline(Duration::from_millis(50));
+    let span_id = context.detach_span(span);
+
+    let span = context.take_detached_span(span_id).unwrap();
+    context.finalize_span(span);
+
+    now.store(200, Ordering::SeqCst);
+    assert_eq!(context.sweep_expired_spans(), 0);
+    assert_eq!(context.spans[0].span_object().is_error, false);
+}
+
+#[test]
+fn display_trace_id_of_generated_id() {
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let expected = format!("{:032x}", context.trace_id.parse::<u128>().unwrap());
+    let displayed = display_trace_id(&context.trace_id);
+
+    assert_eq!(displayed, expected);
+    assert_eq!(displayed.len(), 32);
+
+    assert_eq!(display_trace_id("255"), "000000000000000000000000000000ff");
+    assert_eq!(display_trace_id("a.b-c_d"), "a.b-c_d");
+    assert_eq!(display_trace_id("+1"), "+1");
+}
+
+#[test]
+fn empty_parent_instance_falls_back_to_parent_service() {
+    let propagation = PropagationContext::new(
+        true,
+        "1".to_string(),
+        "5".to_string(),
+        3,
+        "mesh".to_string(),
+        "".to_string(),
+        "/api/v1/health".to_string(),
+        "example.com:8080".to_string(),
+    );
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::from_propagation_context_internal(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        propagation,
+    );
+    let span = context.create_entry_span("op1").unwrap();
+
+    let reference = &span.span_object().refs[0];
+    assert_eq!(reference.parent_service, "mesh");
+    assert_eq!(reference.parent_service_instance, "mesh");
+}
+
+#[derive(Default)]
+struct MockMeterReporter {
+    records: Mutex<Vec<(String, f64, Vec<(String, String)>)>>,
+}
+
+impl MeterReporter for MockMeterReporter {
+    fn record(&self, name: &str, value: f64, labels: &[(String, String)]) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((name.to_string(), value, labels.to_vec()));
+    }
+}
+
+#[test]
+fn record_span_duration_into_meter() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let meter = Arc::new(MockMeterReporter::default());
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_meter_reporter("operation_latency", meter.clone());
+
+    let span = context.create_entry_span("op1").unwrap();
+    now.store(130, Ordering::SeqCst);
+    context.finalize_span(span);
+
+    let records = meter.records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].0, "operation_latency");
+    assert_eq!(records[0].1, 30.0);
+    assert_eq!(
+        records[0].2,
+        vec![("operation".to_string(), "op1".to_string())]
+    );
+}
+
+#[test]
+fn with_span_capacity() {
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance")
+        .with_span_capacity(16);
+
+    assert_eq!(context.spans.capacity() >= 16, true);
+    assert_eq!(context.span_count(), 0);
+}
+
+#[test]
+fn link_parent() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context
+        .link_parent(
+            "trace",
+            "segment",
+            4,
+            "parent-service",
+            "parent-instance",
+            "/parent",
+            "parent:8080",
+        )
+        .unwrap();
+    assert_eq!(context.is_root(), false);
+    assert_eq!(context.trace_id, "trace");
+
+    let span = context.create_entry_span("op1").unwrap();
+    let reference = &span.span_object().refs[0];
+    assert_eq!(reference.ref_type, RefType::CrossProcess as i32);
+    assert_eq!(reference.trace_id, "trace");
+    assert_eq!(reference.parent_trace_segment_id, "segment");
+    assert_eq!(reference.parent_span_id, 4);
+    assert_eq!(reference.parent_service, "parent-service");
+    assert_eq!(reference.parent_service_instance, "parent-instance");
+    assert_eq!(reference.parent_endpoint, "/parent");
+    assert_eq!(reference.network_address_used_at_peer, "parent:8080");
+
+    assert_eq!(
+        context
+            .link_parent("trace", "segment", 4, "s", "i", "/", "a")
+            .is_err(),
+        true
+    );
+}
+
+#[test]
+fn db_exit_span_with_virtual_peer() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _entry = context.create_entry_span("op1").unwrap();
+    let mut span = context
+        .create_db_exit_span(
+            "SELECT",
+            "10.0.0.12:3306",
+            "SELECT * FROM users WHERE id = ?",
+        )
+        .unwrap();
+    assert_eq!(span.span_object().peer, "10.0.0.12:3306");
+
+    span.set_peer("users-db");
+    let span = span.span_object();
+    assert_eq!(span.peer, "users-db");
+    assert_eq!(span.span_type, SpanType::Exit as i32);
+    assert_eq!(span.span_layer, SpanLayer::Database as i32);
+    assert_eq!(span.tags[0].key, DB_STATEMENT_TAG);
+    assert_eq!(span.tags[0].value, "SELECT * FROM users WHERE id = ?");
+    assert_eq!(span.parent_span_id, 0);
+
+    let span = context
+        .create_db_exit_span("INSERT", "10.0.0.12:3306", "INSERT INTO users VALUES (?)")
+        .unwrap();
+    assert_eq!(span.span_object().parent_span_id, 0);
+}
+
+#[test]
+fn health_check_entry() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span = context.health_check_entry("/healthz").unwrap();
+    context.finalize_span(span);
+
+    let span = context.spans[0].span_object();
+    assert_eq!(span.skip_analysis, true);
+    assert_eq!(span.span_type, SpanType::Entry as i32);
+    assert_eq!(span.tags.is_empty(), true);
+    assert_eq!(span.logs.is_empty(), true);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sdk_tags(true);
+    let span = context.health_check_entry("/healthz").unwrap();
+    assert_eq!(span.span_object().skip_analysis, true);
+    assert_eq!(span.tags().is_empty(), true);
+    context.finalize_span(span);
+}
+
+#[derive(Default)]
+struct MockReporter {
+    segments: Vec<rs2sky::skywalking_proto::v3::SegmentObject>,
+}
+
+impl Reporter for MockReporter {
+    fn report(
+        &mut self,
+        segment: rs2sky::skywalking_proto::v3::SegmentObject,
+    ) -> rs2sky::Result<()> {
+        self.segments.push(segment);
+        Ok(())
+    }
+}
+
+#[test]
+fn report_partial() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut reporter = MockReporter::default();
+    let entry = context.create_entry_span("stream").unwrap();
+    let child = context.create_local_span("message1").unwrap();
+    context.finalize_span(child);
+
+    context.report_partial(&mut reporter).unwrap();
+    assert_eq!(reporter.segments.len(), 1);
+    let partial = &reporter.segments[0];
+    assert_eq!(partial.is_size_limited, false);
+    assert_eq!(partial.trace_id, context.trace_id);
+    assert_eq!(partial.trace_segment_id, context.trace_segment_id);
+    assert_eq!(partial.spans.len(), 1);
+    assert_eq!(partial.spans[0].operation_name, "message1");
+
+    let child = context.create_local_span("message2").unwrap();
+    context.finalize_span(child);
+    context.report_partial(&mut reporter).unwrap();
+    assert_eq!(reporter.segments.len(), 2);
+    let partial = &reporter.segments[1];
+    assert_eq!(partial.trace_segment_id, context.trace_segment_id);
+    assert_eq!(partial.spans.len(), 1);
+    assert_eq!(partial.spans[0].operation_name, "message2");
+
+    context.finalize_span(entry);
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.trace_segment_id, context.trace_segment_id);
+    assert_eq!(segment.spans.len(), 1);
+    assert_eq!(segment.spans[0].operation_name, "stream");
+    reporter.segments.push(segment);
+
+    // Parents of the spans are found in the pieces of the same segment.
+    let spans: HashMap<(String, i32), &str> = reporter
+        .segments
+        .iter()
+        .flat_map(|segment| {
+            segment.spans.iter().map(move |span| {
+                (
+                    (segment.trace_segment_id.clone(), span.span_id),
+                    span.operation_name.as_str(),
+                )
+            })
+        })
+        .collect();
+    assert_eq!(spans.len(), 3);
+    for segment in reporter.segments.iter() {
+        for span in segment.spans.iter().filter(|span| span.parent_span_id >= 0) {
+            let parent = spans.get(&(segment.trace_segment_id.clone(), span.parent_span_id));
+            assert_eq!(parent, Some(&"stream"));
+        }
+    }
+}
+
+#[test]
+fn cache_exit_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let _entry = context.create_entry_span("op1").unwrap();
+    let span = context
+        .create_cache_exit_span("redis:6379", "Redis", "GET", "user:1")
+        .unwrap();
+
+    let span = span.span_object();
+    assert_eq!(span.operation_name, "Redis/GET");
+    assert_eq!(span.peer, "redis:6379");
+    assert_eq!(span.span_type, SpanType::Exit as i32);
+    assert_eq!(span.span_layer, SpanLayer::Cache as i32);
+    assert_eq!(span.component_id, 7);
+    assert_eq!(span.tags.len(), 3);
+    assert_eq!(span.tags[0].key, CACHE_TYPE_TAG);
+    assert_eq!(span.tags[0].value, "Redis");
+    assert_eq!(span.tags[1].key, CACHE_OP_TAG);
+    assert_eq!(span.tags[1].value, "GET");
+    assert_eq!(span.tags[2].key, CACHE_KEY_TAG);
+    assert_eq!(span.tags[2].value, "user:1");
+
+    // The first exit span is still open, but they are siblings.
+    let key = "k".repeat(200);
+    let span = context
+        .create_cache_exit_span("redis:6379", "Redis", "GET", &key)
+        .unwrap();
+    assert_eq!(span.span_object().tags[2].value.len(), 128);
+    assert_eq!(span.span_object().parent_span_id, 0);
+}
+
+#[test]
+fn into_segment() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    let exit = context.create_exit_span("op2", "example.com").unwrap();
+    context.finalize_span(exit);
+    context.finalize_span(entry);
+
+    let expected = context.convert_segment_object();
+    let segment = context.into_segment();
+    assert_eq!(segment, expected);
+    assert_eq!(segment.spans.len(), 2);
+}
+
+#[test]
+fn entry_span_with_source_location() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span = rs2sky::entry_span!(context, "op1").unwrap();
+
+    let tag = &span.span_object().tags[0];
+    assert_eq!(tag.key, SOURCE_LOCATION_TAG);
+    assert_eq!(tag.value.contains("trace_context.rs:"), true);
+    assert_eq!(span.span_object().span_type, SpanType::Entry as i32);
+}
+
+#[test]
+fn sibling_span_ids() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    let exit1 = context.create_exit_span("op2", "remote_peer").unwrap();
+    context.finalize_span(exit1);
+    let exit2 = context.create_exit_span("op3", "remote_peer").unwrap();
+    context.finalize_span(exit2);
+    context.finalize_span(entry);
+
+    let segment = context.convert_segment_object();
+    let ids: Vec<(i32, i32)> = segment
+        .spans
+        .iter()
+        .map(|span| (span.span_id, span.parent_span_id))
+        .collect();
+    assert_eq!(ids, vec![(0, -1), (1, 0), (2, 0)]);
+}
+
+#[test]
+fn namespace() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::default_internal(Arc::new(time_fetcher), "svc", "instance");
+    context.set_namespace("ns");
+    let span = context.create_entry_span("op1").unwrap();
+    context.finalize_span(span);
+
+    assert_eq!(context.service, "svc");
+    assert_eq!(context.convert_segment_object().service, "ns::svc");
+
+    let prop = decode_propagation(&encode_propagation(&context, None, None)).unwrap();
+    assert_eq!(prop.parent_service, "ns::svc");
+
+    assert_eq!(context.into_segment().service, "ns::svc");
+}
+
+fn call_with_exit_span(context: &mut TracingContext, fail: bool) -> Result<i32, &'static str> {
+    let value = rs2sky::with_exit_span!(context, "op2", "remote_peer", {
+        if fail {
+            return Err("failed");
+        }
+        42
+    });
+    Ok(value)
+}
+
+#[test]
+fn with_exit_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+
+    assert_eq!(call_with_exit_span(&mut context, false), Ok(42));
+    assert_eq!(context.span_count(), 1);
+    assert_eq!(
+        context.spans[0].span_object().span_type,
+        SpanType::Exit as i32
+    );
+
+    assert_eq!(call_with_exit_span(&mut context, true), Err("failed"));
+    assert_eq!(context.span_count(), 2);
+    assert_eq!(context.active_span_count(), 1);
+
+    context.finalize_span(entry);
+    assert_eq!(context.active_span_count(), 0);
+}
+
+#[test]
+fn with_entry_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let value = rs2sky::with_entry_span!(context, "op1", { "done" });
+
+    assert_eq!(value, "done");
+    assert_eq!(context.span_count(), 1);
+    assert_eq!(context.active_span_count(), 0);
+}
+
+#[test]
+fn with_span_without_entry_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+
+    // The exit span can't be created before the entry span, but the body runs.
+    assert_eq!(call_with_exit_span(&mut context, false), Ok(42));
+    assert_eq!(context.span_count(), 0);
+    assert_eq!(context.active_span_count(), 0);
+
+    let value = rs2sky::with_entry_span!(context, "op1", { "done" });
+    assert_eq!(value, "done");
+    context.convert_segment_object();
+
+    // The context is already reported.
+    let value = rs2sky::with_entry_span!(context, "op1", { "done again" });
+    assert_eq!(value, "done again");
+    assert_eq!(context.span_count(), 1);
+}
+
+#[test]
+fn self_time_tags() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    now.store(120, Ordering::SeqCst);
+    let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+    now.store(150, Ordering::SeqCst);
+    context.finalize_span(exit);
+    now.store(200, Ordering::SeqCst);
+    context.finalize_span(entry);
+
+    context.add_self_time_tags();
+    let segment = context.convert_segment_object();
+    let self_time = |span: &rs2sky::skywalking_proto::v3::SpanObject| {
+        span.tags
+            .iter()
+            .find(|tag| tag.key == SELF_TIME_TAG)
+            .map(|tag| tag.value.clone())
+    };
+    assert_eq!(self_time(&segment.spans[0]), Some("70".to_string()));
+    assert_eq!(self_time(&segment.spans[1]), Some("30".to_string()));
+}
+
+#[derive(Debug)]
+struct MockError {
+    message: &'static str,
+    source: Option<Box<MockError>>,
+}
+
+impl std::fmt::Display for MockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[test]
+fn record_error() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    let err: Box<dyn std::error::Error> = Box::new(MockError {
+        message: "request failed",
+        source: Some(Box::new(MockError {
+            message: "connection refused",
+            source: None,
+        })),
+    });
+    span.record_error(err.as_ref());
+
+    assert_eq!(span.span_object().is_error, true);
+    assert_eq!(span.tags()[0].key, "error");
+    assert_eq!(span.tags()[0].value, "true");
+
+    let data = &span.logs()[0].data;
+    assert_eq!(data[0].key, "event");
+    assert_eq!(data[0].value, "error");
+    assert_eq!(data[1].key, "message");
+    assert_eq!(data[1].value, "request failed");
+    assert_eq!(data[2].key, "stack");
+    assert_eq!(
+        data[2].value,
+        "request failed\ncaused by: connection refused"
+    );
+}
+
+#[test]
+fn snapshot_and_restore() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("/offload").unwrap();
+    let snapshot = context.snapshot();
+
+    let worker = std::thread::spawn(move || {
+        let time_fetcher = MockTimeFetcher {};
+        let mut worker = TracingContext::restore(snapshot, Arc::new(time_fetcher));
+        let span = worker.create_entry_span("work").unwrap();
+        worker.finalize_span(span);
+        worker.convert_segment_object()
+    });
+    let segment = worker.join().unwrap();
+    context.finalize_span(entry);
+
+    assert_eq!(segment.trace_id, context.trace_id);
+    assert_ne!(segment.trace_segment_id, context.trace_segment_id);
+    assert_eq!(segment.service, "service");
+
+    let reference = &segment.spans[0].refs[0];
+    assert_eq!(reference.ref_type, RefType::CrossThread as i32);
+    assert_eq!(reference.trace_id, context.trace_id);
+    assert_eq!(reference.parent_trace_segment_id, context.trace_segment_id);
+    assert_eq!(reference.parent_span_id, 0);
+    assert_eq!(reference.parent_service, "service");
+    assert_eq!(reference.parent_service_instance, "instance");
+    assert_eq!(reference.parent_endpoint, "/offload");
+}
+
+#[test]
+fn add_cross_thread_reference() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    let link = PropagationContext::new(
+        true,
+        context.trace_id.clone(),
+        "segment".to_string(),
+        2,
+        "service".to_string(),
+        "instance".to_string(),
+        "/parent".to_string(),
+        "".to_string(),
+    );
+    span.add_reference(
+        &context.trace_id,
+        &link,
+        rs2sky::skywalking_proto::v3::RefType::CrossThread,
+    );
+
+    let reference = &span.span_object().refs[0];
+    assert_eq!(reference.ref_type, RefType::CrossThread as i32);
+    assert_eq!(reference.parent_trace_segment_id, "segment");
+    assert_eq!(reference.parent_span_id, 2);
+}
+
+#[test]
+fn sdk_tags() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sdk_tags(true);
+    let entry = context.create_entry_span("op1").unwrap();
+    let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+
+    assert_eq!(entry.tags()[0].key, SDK_LANGUAGE_TAG);
+    assert_eq!(entry.tags()[0].value, "Rust");
+    assert_eq!(entry.tags()[1].key, SDK_VERSION_TAG);
+    assert_eq!(entry.tags()[1].value, env!("CARGO_PKG_VERSION"));
+    assert_eq!(exit.tags().is_empty(), true);
+}
+
+#[test]
+fn from_propagation_context_without_ref() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let prop = decode_propagation(data).unwrap();
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::from_propagation_context_without_ref_internal(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        prop,
+    );
+    assert_eq!(context.trace_id, "1");
+    assert_eq!(context.parent_endpoint(), Some("/api/v1/health"));
+
+    let span = context.create_entry_span("op1").unwrap();
+    assert_eq!(span.span_object().refs.is_empty(), true);
+}
+
+#[test]
+fn endpoint_name_without_query() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("/search?q=cat").unwrap();
+    assert_eq!(span.span_object().operation_name, "/search");
+    assert_eq!(context.snapshot().endpoint, "/search");
+
+    span.set_endpoint_name("/users/1#profile");
+    assert_eq!(span.span_object().operation_name, "/users/1");
+}
+
+#[test]
+fn time_offset() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_time_offset_ms(5000);
+    let span = context.create_entry_span("op1").unwrap();
+    now.store(130, Ordering::SeqCst);
+    context.finalize_span(span);
+
+    let span = context.spans[0].span_object();
+    assert_eq!(span.start_time, 5100);
+    assert_eq!(span.end_time, 5130);
+}
+
+#[test]
+fn get_tag() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    span.add_tag(("http.method", "GET"));
+
+    assert_eq!(span.get_tag("http.method"), Some("GET"));
+    assert_eq!(span.get_tag("http.status_code"), None);
+}
+
+#[test]
+fn try_convert_segment_object() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+    context.finalize_span(exit);
+
+    assert_eq!(
+        context.try_convert_segment_object(),
+        Err(vec![SegmentValidationError::SpanNotClosed(0)])
+    );
+
+    let local = context.create_local_span("op3").unwrap();
+    context.finalize_span_at(local, 0);
+    context.finalize_span(entry);
+    assert_eq!(
+        context.try_convert_segment_object(),
+        Err(vec![SegmentValidationError::SpanNotClosed(2)])
+    );
+}
+
+#[test]
+fn try_convert_valid_segment_object() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    context.finalize_span(entry);
+
+    assert_eq!(
+        context.try_convert_segment_object(),
+        Ok(context.convert_segment_object())
+    );
+}
+
+#[tokio::test]
+async fn move_context_across_tasks() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+
+    let handle = tokio::spawn(async move {
+        let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+        context.finalize_span(exit);
+        context.finalize_span(entry);
+        context
+    });
+    let context = handle.await.unwrap();
+
+    assert_eq!(context.spans.len(), 2);
+    assert_eq!(context.try_convert_segment_object().is_ok(), true);
+}
+
+#[test]
+fn exit_spans_with_component() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    let grpc = context
+        .create_exit_span_with_component(
+            "/grpc.Service/Method",
+            "grpc:50051",
+            rs2sky::skywalking_proto::v3::SpanLayer::RpcFramework,
+            Some(23),
+        )
+        .unwrap();
+    context.finalize_span(grpc);
+    let http = context
+        .create_exit_span_with_component(
+            "/api",
+            "http:8080",
+            rs2sky::skywalking_proto::v3::SpanLayer::Http,
+            Some(2),
+        )
+        .unwrap();
+    context.finalize_span(http);
+    let default = context.create_exit_span("/default", "http:8080").unwrap();
+    context.finalize_span(default);
+    context.finalize_span(entry);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans[1].component_id, 23);
+    assert_eq!(segment.spans[1].span_layer, SpanLayer::RpcFramework as i32);
+    assert_eq!(segment.spans[2].component_id, 2);
+    assert_eq!(segment.spans[2].span_layer, SpanLayer::Http as i32);
+    assert_eq!(segment.spans[3].component_id, 11000);
+}
+
+#[test]
+fn propagate_entry_endpoint_across_hops() {
+    let mut front = TracingContext::default_internal(Arc::new(MockTimeFetcher {}), "front", "f1");
+    let front_entry = front.create_entry_span("/checkout").unwrap();
+    let front_exit = front.create_exit_span("/orders", "middle:8080").unwrap();
+    let header = encode_propagation(&front, Some("/orders"), Some("middle:8080"));
+    front.finalize_span(front_exit);
+    front.finalize_span(front_entry);
+
+    let mut middle = TracingContext::from_propagation_context_internal(
+        Arc::new(MockTimeFetcher {}),
+        "middle",
+        "m1",
+        decode_propagation(&header).unwrap(),
+    );
+    let middle_entry = middle.create_entry_span("/orders").unwrap();
+    let middle_exit = middle.create_exit_span("/stock", "back:8080").unwrap();
+    let header = encode_propagation(&middle, Some("/stock"), Some("back:8080"));
+    middle.finalize_span(middle_exit);
+    middle.finalize_span(middle_entry);
+
+    let mut back = TracingContext::from_propagation_context_internal(
+        Arc::new(MockTimeFetcher {}),
+        "back",
+        "b1",
+        decode_propagation(&header).unwrap(),
+    );
+    let back_entry = back.create_entry_span("/stock").unwrap();
+    back.finalize_span(back_entry);
+
+    let middle_segment = middle.convert_segment_object();
+    let reference = &middle_segment.spans[0].refs[0];
+    assert_eq!(reference.parent_service, "front");
+    assert_eq!(reference.parent_endpoint, "/checkout");
+    assert_eq!(reference.network_address_used_at_peer, "middle:8080");
+
+    let back_segment = back.convert_segment_object();
+    let reference = &back_segment.spans[0].refs[0];
+    assert_eq!(reference.trace_id, front.trace_id);
+    assert_eq!(reference.parent_service, "middle");
+    assert_eq!(reference.parent_endpoint, "/orders");
+    assert_eq!(reference.network_address_used_at_peer, "back:8080");
+}
+
+#[test]
+fn close_span_once() {
+    let now = Arc::new(AtomicI64::new(100));
+    let time_fetcher = StepTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+
+    now.store(150, Ordering::SeqCst);
+    span.close();
+    assert_eq!(span.span_object().end_time, 150);
+
+    now.store(300, Ordering::SeqCst);
+    span.close();
+    assert_eq!(span.span_object().end_time, 150);
+
+    context.finalize_span(span);
+    assert_eq!(context.spans[0].span_object().end_time, 150);
+}
+
+#[test]
+fn cap_references() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_max_references(2);
+    let mut span = context.create_entry_span("consume").unwrap();
+    for i in 0..5 {
+        let link = PropagationContext::new(
+            true,
+            format!("trace-{}", i),
+            format!("segment-{}", i),
+            0,
+            "producer".to_string(),
+            "instance".to_string(),
+            "/produce".to_string(),
+            "broker:9092".to_string(),
+        );
+        span.add_reference(
+            &link.parent_trace_id,
+            &link,
+            rs2sky::skywalking_proto::v3::RefType::CrossProcess,
+        );
+    }
+
+    assert_eq!(span.span_object().refs.len(), 2);
+    assert_eq!(span.span_object().refs[1].trace_id, "trace-1");
+    assert_eq!(span.get_tag(REFS_TRUNCATED_TAG), Some("true"));
+    assert_eq!(span.tags().len(), 1);
+    context.finalize_span(span);
+}
+
+#[test]
+fn from_borrowed_propagation_context() {
+    let data = "1-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let prop = decode_propagation(data).unwrap();
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::from_propagation_context_ref_internal(
+        Arc::new(time_fetcher),
+        "service2",
+        "instance2",
+        &prop,
+    );
+
+    assert_eq!(prop.parent_trace_id, "1");
+    assert_eq!(prop.parent_service, "mesh");
+    assert_eq!(context.trace_id, prop.parent_trace_id);
+    assert_eq!(
+        context.parent_endpoint(),
+        Some(prop.destination_endpoint.as_str())
+    );
+
+    let span = context.create_entry_span("op1").unwrap();
+    let reference = &span.span_object().refs[0];
+    assert_eq!(
+        reference.parent_trace_segment_id,
+        prop.parent_trace_segment_id
+    );
+    assert_eq!(reference.parent_span_id, prop.parent_span_id);
+    context.finalize_span(span);
+}
+
+#[test]
+fn force_report_with_sampling_priority() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sampled(false);
+    let span = context.create_entry_span("/health").unwrap();
+    context.finalize_span(span);
+    assert_eq!(context.should_report(), false);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sampled(false);
+    let mut span = context.create_entry_span("/checkout").unwrap();
+    span.set_sampling_priority(true).unwrap();
+    span.add_tag(("order", "1234"));
+    let exit = context.create_exit_span("/pay", "payment:8080").unwrap();
+    assert_eq!(exit.is_noop(), false);
+    context.finalize_span(exit);
+    context.finalize_span(span);
+    assert_eq!(context.is_sampled(), false);
+    assert_eq!(context.should_report(), true);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 2);
+    assert_eq!(segment.spans[0].operation_name, "/checkout");
+    assert_eq!(segment.spans[0].span_id, 0);
+    assert_eq!(segment.spans[0].tags.len(), 1);
+    assert_eq!(segment.spans[0].tags[0].key, "order");
+    assert_eq!(segment.spans[0].tags[0].value, "1234");
+    assert_eq!(segment.spans[1].operation_name, "/pay");
+    assert_eq!(segment.spans[1].parent_span_id, 0);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sampled(false);
+    let mut span = context.create_entry_span("/checkout").unwrap();
+    span.add_tag(("lost", "true"));
+    assert_eq!(span.set_sampling_priority(true).is_err(), true);
+    assert_eq!(span.is_noop(), false);
+    context.finalize_span(span);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 1);
+    assert_eq!(segment.spans[0].operation_name, "/checkout");
+    assert_eq!(segment.spans[0].tags.is_empty(), true);
+}
+
+#[test]
+fn keep_continued_trace_with_sampling_priority() {
+    let data = "0-MQ==-NQ==-3-bWVzaA==-aW5zdGFuY2U=-L2FwaS92MS9oZWFsdGg=-ZXhhbXBsZS5jb206ODA4MA==";
+    let prop = decode_propagation(data).unwrap();
+    let time_fetcher = MockTimeFetcher {};
+    let mut context = TracingContext::from_propagation_context_internal(
+        Arc::new(time_fetcher),
+        "service",
+        "instance",
+        prop,
+    );
+    let mut span = context.create_entry_span("/checkout").unwrap();
+    assert_eq!(span.span_object().refs.is_empty(), true);
+    span.set_sampling_priority(true).unwrap();
+    context.finalize_span(span);
+
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 1);
+    assert_eq!(segment.spans[0].refs.len(), 1);
+    assert_eq!(segment.spans[0].refs[0].parent_span_id, 3);
+}
+
+#[test]
+fn unsampled_spans_are_noop() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_sampled(false);
+    context.set_peer_from_url(true);
+    context.set_peer_tags(true);
+
+    let mut entry = context.create_entry_span("/checkout?id=1").unwrap();
+    entry.set_endpoint_name("/checkout/{id}");
+    entry.set_source_location("src/main.rs", 42);
+    entry.add_tag(("order", "1234"));
+    entry.add_log(vec![("event", "start")]);
+    assert_eq!(entry.is_noop(), true);
+    assert_eq!(entry.span_object().span_id, 0);
+    assert_eq!(entry.span_object().operation_name.is_empty(), true);
+    assert_eq!(entry.tags().is_empty(), true);
+    assert_eq!(entry.logs().is_empty(), true);
+
+    let mut exit = context
+        .create_exit_span("/pay", "http://payment:8080/pay")
+        .unwrap();
+    exit.set_peer("payment");
+    exit.tag_grpc_status(14);
+    assert_eq!(exit.is_noop(), true);
+    assert_eq!(exit.span_object().parent_span_id, 0);
+    assert_eq!(exit.span_object().operation_name.is_empty(), true);
+    assert_eq!(exit.span_object().peer.is_empty(), true);
+    assert_eq!(exit.tags().is_empty(), true);
+    context.finalize_span(exit);
+
+    let cache = context
+        .create_cache_exit_span("redis:6379", "redis", "GET", "user:1")
+        .unwrap();
+    assert_eq!(cache.is_noop(), true);
+    assert_eq!(cache.span_object().operation_name.is_empty(), true);
+    assert_eq!(cache.span_object().peer.is_empty(), true);
+    assert_eq!(cache.tags().is_empty(), true);
+    context.finalize_span(cache);
+    context.finalize_span(entry);
+
+    assert_eq!(context.span_count(), 0);
+}
+
+#[test]
+fn tag_grpc_status() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+
+    let mut ok = context
+        .create_exit_span("/grpc.Service/Ok", "grpc:50051")
+        .unwrap();
+    ok.tag_grpc_status(0);
+    assert_eq!(ok.get_tag(RPC_STATUS_CODE_TAG), Some("0"));
+    assert_eq!(ok.span_object().is_error, false);
+    context.finalize_span(ok);
+
+    let mut unavailable = context
+        .create_exit_span("/grpc.Service/Unavailable", "grpc:50051")
+        .unwrap();
+    unavailable.tag_grpc_status(14);
+    assert_eq!(unavailable.get_tag(RPC_STATUS_CODE_TAG), Some("14"));
+    assert_eq!(unavailable.span_object().is_error, true);
+    context.finalize_span(unavailable);
+    context.finalize_span(entry);
+}
+
+#[test]
+fn host_instance() {
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::with_host_instance(Arc::new(time_fetcher), "service");
+    assert_eq!(context.service, "service");
+    assert_eq!(
+        context
+            .service_instance
+            .ends_with(&format!("@{}", std::process::id())),
+        true
+    );
+    assert_eq!(
+        context.service_instance.len() > 1 + std::process::id().to_string().len(),
+        true
+    );
+}
+
+struct NanosTimeFetcher {
+    now: Arc<AtomicI64>,
+}
+
+impl TimeFetcher for NanosTimeFetcher {
+    fn get(&self) -> i64 {
+        self.now.load(Ordering::SeqCst) / 1_000_000
+    }
+
+    fn get_nanos(&self) -> i128 {
+        self.now.load(Ordering::SeqCst) as i128
+    }
+
+    fn get_monotonic_nanos(&self) -> i128 {
+        self.now.load(Ordering::SeqCst) as i128
+    }
+}
+
+#[test]
+fn span_time_in_nanos() {
+    let now = Arc::new(AtomicI64::new(100_000_123));
+    let time_fetcher = NanosTimeFetcher { now: now.clone() };
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let mut span = context.create_entry_span("op1").unwrap();
+    assert_eq!(span.span_object().start_time, 100);
+    assert_eq!(span.start_time_nanos(), 100_000_123);
+    assert_eq!(span.end_time_nanos(), 0);
+
+    now.store(102_500_456, Ordering::SeqCst);
+    span.close();
+    assert_eq!(span.span_object().end_time, 102);
+    assert_eq!(span.end_time_nanos(), 102_500_456);
+    context.finalize_span(span);
+
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let span = context.create_entry_span("op1").unwrap();
+    assert_eq!(span.start_time_nanos(), 100_000_000);
+    context.finalize_span(span);
+}
+
+#[test]
+fn unix_time_in_nanos() {
+    let time_fetcher = UnixTimeStampFetcher::default();
+    let before = time_fetcher.get() as i128 * 1_000_000;
+    let nanos = time_fetcher.get_nanos();
+    let after = (time_fetcher.get() + 1) as i128 * 1_000_000;
+    assert_eq!(before <= nanos && nanos < after, true);
+
+    let start = time_fetcher.get_monotonic_nanos();
+    std::thread::sleep(Duration::from_millis(1));
+    assert_eq!(
+        time_fetcher.get_monotonic_nanos() - start >= 1_000_000,
+        true
+    );
+}
+
+#[test]
+fn reset_context() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    context.set_namespace("ns");
+    context.set_local("key", "value");
+    let entry = context.create_entry_span("op1").unwrap();
+    let exit = context.create_exit_span("op2", "remote_peer").unwrap();
+    context.finalize_span(exit);
+    let trace_id = context.trace_id.clone();
+    let trace_segment_id = context.trace_segment_id.clone();
+
+    context.reset();
+    drop(entry);
+    assert_ne!(context.trace_id, trace_id);
+    assert_ne!(context.trace_segment_id, trace_segment_id);
+    assert_eq!(context.next_span_id, 0);
+    assert_eq!(context.spans.is_empty(), true);
+    assert_eq!(context.active_span_id(), None);
+    assert_eq!(context.get_local("key"), None);
+    assert_eq!(context.is_root(), true);
+    assert_eq!(context.namespaced_service(), "ns::service");
+
+    let entry = context.create_entry_span("op3").unwrap();
+    assert_eq!(entry.span_object().span_id, 0);
+    assert_eq!(entry.span_object().parent_span_id, -1);
+    context.finalize_span(entry);
+    assert_eq!(context.convert_segment_object().spans.len(), 1);
+}
+
+#[test]
+fn create_span_after_reported() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    context.finalize_span(entry);
+    let segment = context.convert_segment_object();
+    assert_eq!(segment.spans.len(), 1);
+
+    let err = context
+        .create_exit_span("op2", "remote_peer")
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), CONTEXT_ALREADY_REPORTED);
+    assert_eq!(context.create_local_span("op3").is_err(), true);
+    assert_eq!(context.convert_segment_object(), segment);
+
+    context.reset();
+    assert_eq!(context.create_entry_span("op1").is_ok(), true);
+}
+
+#[test]
+fn size_limited_segment() {
+    let time_fetcher = MockTimeFetcher {};
+    let mut context =
+        TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let entry = context.create_entry_span("op1").unwrap();
+    context.finalize_span(entry);
+    assert_eq!(context.convert_segment_object().is_size_limited, false);
+
+    context.set_size_limited(true);
+    assert_eq!(context.convert_segment_object().is_size_limited, true);
+    assert_eq!(context.into_segment().is_size_limited, true);
+}
@@ -0,0 +1,111 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![cfg(feature = "tracing")]
+
+use rs2sky::common::time::TimeFetcher;
+use rs2sky::context::trace_context::TracingContext;
+use rs2sky::context::tracing_ext::{record_context, SEGMENT_ID_FIELD, TRACE_ID_FIELD};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+struct MockTimeFetcher {}
+
+impl TimeFetcher for MockTimeFetcher {
+    fn get(&self) -> i64 {
+        100
+    }
+}
+
+/// Subscriber which only keeps the values recorded onto spans.
+#[derive(Default, Clone)]
+struct RecordingSubscriber {
+    fields: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+struct FieldVisitor<'a> {
+    fields: &'a mut Vec<(String, String)>,
+}
+
+impl<'a> Visit for FieldVisitor<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields
+            .push((field.name().to_string(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl Subscriber for RecordingSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, values: &Record<'_>) {
+        let mut fields = self.fields.lock().unwrap();
+        values.record(&mut FieldVisitor {
+            fields: &mut fields,
+        });
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn record_ids_onto_tracing_span() {
+    let time_fetcher = MockTimeFetcher {};
+    let context = TracingContext::default_internal(Arc::new(time_fetcher), "service", "instance");
+    let subscriber = RecordingSubscriber::default();
+
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = tracing::info_span!(
+            "request",
+            sw.trace_id = tracing::field::Empty,
+            sw.segment_id = tracing::field::Empty
+        );
+        let _enter = span.enter();
+        record_context(&span, &context);
+    });
+
+    let fields = subscriber.fields.lock().unwrap();
+    assert_eq!(
+        fields.contains(&(TRACE_ID_FIELD.to_string(), context.trace_id.clone())),
+        true
+    );
+    assert_eq!(
+        fields.contains(&(
+            SEGMENT_ID_FIELD.to_string(),
+            context.trace_segment_id.clone()
+        )),
+        true
+    );
+}
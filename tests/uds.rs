@@ -0,0 +1,88 @@
+// Licensed to the Apache Software Foundation (ASF) under one or more
+// contributor license agreements.  See the NOTICE file distributed with
+// this work for additional information regarding copyright ownership.
+// The ASF licenses this file to You under the Apache License, Version 2.0
+// (the "License"); you may not use this file except in compliance with
+// the License.  You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![cfg(all(feature = "uds", unix))]
+
+use rs2sky::reporter::grpc::GrpcReporter;
+use rs2sky::reporter::reporter_trait::Reporter;
+use rs2sky::skywalking_proto::v3::trace_segment_report_service_server::{
+    TraceSegmentReportService, TraceSegmentReportServiceServer,
+};
+use rs2sky::skywalking_proto::v3::{Commands, SegmentCollection, SegmentObject};
+use std::sync::{Arc, Mutex};
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+#[derive(Default, Clone)]
+struct MockCollector {
+    segments: Arc<Mutex<Vec<SegmentObject>>>,
+}
+
+#[tonic::async_trait]
+impl TraceSegmentReportService for MockCollector {
+    async fn collect(
+        &self,
+        request: Request<Streaming<SegmentObject>>,
+    ) -> Result<Response<Commands>, Status> {
+        let mut stream = request.into_inner();
+        while let Some(segment) = stream.message().await? {
+            self.segments.lock().unwrap().push(segment);
+        }
+        Ok(Response::new(Commands::default()))
+    }
+
+    async fn collect_in_sync(
+        &self,
+        _request: Request<SegmentCollection>,
+    ) -> Result<Response<Commands>, Status> {
+        Ok(Response::new(Commands::default()))
+    }
+}
+
+#[tokio::test]
+async fn report_through_uds() {
+    let path = std::env::temp_dir().join(format!("rs2sky-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let collector = MockCollector::default();
+    let listener = UnixListener::bind(&path).unwrap();
+    let server = collector.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(TraceSegmentReportServiceServer::new(server))
+            .serve_with_incoming(UnixListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+
+    let mut reporter = GrpcReporter::connect_uds(&path).await.unwrap();
+    reporter
+        .report(SegmentObject {
+            trace_id: "trace".to_string(),
+            trace_segment_id: "1".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    reporter.flush().await.unwrap();
+
+    let segments = collector.segments.lock().unwrap();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].trace_segment_id, "1");
+
+    let _ = std::fs::remove_file(&path);
+}